@@ -1,15 +1,40 @@
 in vec3 position;
 in vec3 embed;
+in vec3 normal;
+in vec3 line_end;
+in vec2 corner;
 uniform mat4 model;
 uniform mat4 meta;
 uniform mat4 view;
+// 0 draws triangles, 1 widens line quads and 2 widens point quads, see `DrawMode`
+uniform int primitive;
+uniform float width;
+uniform vec2 viewport;
 out vec3 pos;
+out vec3 v_normal;
+out vec3 v_view;
 
 void main() {
     vec4 p = vec4(position, 1.0);
     vec4 model_pos = model * p;
     gl_Position = view * model * p;
+    if (primitive == 1) {
+        vec4 end = view * model * vec4(line_end, 1.0);
+        vec2 along = (end.xy / end.w - gl_Position.xy / gl_Position.w) * viewport;
+        vec2 across = length(along) > 0.0 ? normalize(vec2(-along.y, along.x)) : vec2(0.0);
+        gl_Position.xy += across * corner.x * width / viewport * gl_Position.w;
+    } else if (primitive == 2) {
+        gl_Position.xy += corner * width / viewport * gl_Position.w;
+    }
+    if (primitive != 0) {
+        // Lines and points drawn on a surface stay in front of it
+        gl_Position.z -= 0.001 * gl_Position.w;
+    }
     vec4 meta_pos = meta * vec4(embed, 1.0);
     pos = meta_pos.xyz;
     // pos = model_pos.xyz;
-}
\ No newline at end of file
+    v_normal = mat3(model) * normal;
+    // The camera is the point the projection sends to infinity
+    vec4 eye = inverse(view) * vec4(0.0, 0.0, -1.0, 0.0);
+    v_view = abs(eye.w) > 1e-6 ? eye.xyz / eye.w - model_pos.xyz : eye.xyz;
+}
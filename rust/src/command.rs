@@ -0,0 +1,106 @@
+use std::rc::Rc;
+
+use three_d::{vec3, Program, Vec3};
+
+use crate::{
+    camera::CustomController,
+    element::{
+        colorspace::SpaceDisplay, histogram::HistogramDisplay, isolines::IsoSource, Shading,
+    },
+    embed::Embedding,
+    input::InputState,
+    mesh::{clip::Plane, normals::Normals, CpuMesh},
+    photo::Photo,
+};
+
+/// A change to the view requested from outside the render loop, applied at the start of the
+/// next frame.
+pub enum Command {
+    /// Selects an input position of the color embedding.
+    Pos(Vec3),
+    /// Selects the position showing an Oklab color.
+    Color(Vec3),
+    Chunk(Vec3),
+    ColorEmbedding(Rc<dyn Embedding<Vec3>>),
+    SpaceEmbedding(Rc<dyn Embedding<Vec3>>),
+    Camera {
+        position: Vec3,
+        target: Vec3,
+        up: Vec3,
+    },
+    Display(SpaceDisplay),
+    Shading(Shading),
+    Normals(Normals),
+    IsoLines(bool),
+    ExtraIsoLines(Option<(IsoSource, f32)>),
+    Solid(Rc<CpuMesh>),
+    Clip(Option<Plane>),
+    Photo(Option<Rc<Photo>>),
+    Histogram(Option<HistogramDisplay>),
+    /// Oklab colors shown in place of the color chips, none for the chips.
+    Palette(Vec<Vec3>),
+    /// Replaces the program coloring the solid, compiled when queued.
+    ColorProgram(Box<Program>),
+}
+
+/// Brings `pos` into `0..1` along the axes of `embedding`.
+fn wrap(embedding: &dyn Embedding<Vec3>, pos: Vec3) -> Vec3 {
+    let axes = embedding.axes();
+    vec3(
+        axes[0].wrap(pos.x),
+        axes[1].wrap(pos.y),
+        axes[2].wrap(pos.z),
+    )
+}
+
+impl Command {
+    pub fn apply(
+        self,
+        state: &mut InputState,
+        control: &mut CustomController,
+        program: &mut Program,
+    ) {
+        match self {
+            Command::Pos(pos) => {
+                // The selection falls back to the saved position whenever nothing is hovered
+                state.pos = wrap(state.color_embedding.as_ref(), pos);
+                state.saved_pos = state.pos;
+                state.color = state.color_embedding.embed(state.pos);
+            }
+            Command::Color(color) => {
+                let pos = state.color_embedding.invert(color);
+                state.pos = wrap(state.color_embedding.as_ref(), pos);
+                state.saved_pos = state.pos;
+                state.color = color;
+            }
+            Command::Chunk(chunk) => {
+                state.chunk = chunk.map(|c| c.clamp(0.0, 1.0));
+            }
+            Command::ColorEmbedding(embedding) => {
+                state.color_embedding = embedding;
+            }
+            Command::SpaceEmbedding(embedding) => {
+                state.space_embedding = embedding;
+            }
+            Command::Camera {
+                position,
+                target,
+                up,
+            } => {
+                state.camera.set_view(position, target, up);
+                *control = CustomController::new(target, 1.0, 100.0);
+            }
+            Command::Display(display) => state.space_display = display,
+            Command::Shading(shading) => state.shading = shading,
+            Command::Normals(normals) => state.normals = normals,
+            Command::IsoLines(enabled) => state.iso_lines = enabled,
+            Command::ExtraIsoLines(lines) => state.extra_iso_lines = lines,
+            Command::Solid(solid) => state.solid = solid,
+            Command::Clip(clip) => state.clip = clip,
+            Command::Photo(photo) => state.photo = photo,
+            Command::Histogram(histogram) => state.histogram = histogram,
+            Command::Palette(palette) => state.palette = palette,
+            Command::ColorProgram(color_program) => *program = *color_program,
+        }
+    }
+}
@@ -0,0 +1,1024 @@
+use std::f32::consts::PI;
+
+use cgmath::{vec2, InnerSpace};
+use derive_more::Display;
+use palette::{white_point::D50, FromColor, Lab, Xyz};
+use three_d::{vec3, Vec3};
+
+use crate::embed::{graph, Embedding};
+
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum CssError {
+    #[display(fmt = "{}", _0)]
+    Syntax(String),
+    #[display(fmt = "unknown color space {}", _0)]
+    UnknownSpace(String),
+}
+
+fn syntax<T>(message: impl Into<String>) -> Result<T, CssError> {
+    Err(CssError::Syntax(message.into()))
+}
+
+/// A color read from or written as CSS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CssColor {
+    pub oklab: Vec3,
+    /// In `0..1`.
+    pub alpha: f32,
+}
+
+impl CssColor {
+    pub fn new(oklab: Vec3, alpha: f32) -> Self {
+        Self { oklab, alpha }
+    }
+
+    /// The color at input position `pos` of `embedding`, which maps inputs to Oklab.
+    pub fn from_position(pos: Vec3, embedding: &dyn Embedding<Vec3>) -> Self {
+        Self::new(embedding.embed(pos), 1.0)
+    }
+}
+
+/// The spaces of CSS color functions, each with its own channel units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CssSpace {
+    /// Channels in `0..255`.
+    Rgb,
+    /// Hue in degrees, saturation and lightness in `0..100`.
+    Hsl,
+    /// Hue in degrees, whiteness and blackness in `0..100`.
+    Hwb,
+    /// CIELAB under D50.
+    Lab,
+    Lch,
+    Oklab,
+    Oklch,
+    /// A space of `color()`, by its CSS name or the name of any space of the conversion graph.
+    /// Every predefined CSS space is known: `srgb`, `srgb-linear`, `display-p3`, `rec2020`,
+    /// `a98-rgb`, `prophoto-rgb`, `xyz`, `xyz-d50` and `xyz-d65`.
+    Color(String),
+}
+
+impl CssSpace {
+    fn from_function(name: &str) -> Option<Self> {
+        match name {
+            "rgb" | "rgba" => Some(Self::Rgb),
+            "hsl" | "hsla" => Some(Self::Hsl),
+            "hwb" => Some(Self::Hwb),
+            "lab" => Some(Self::Lab),
+            "lch" => Some(Self::Lch),
+            "oklab" => Some(Self::Oklab),
+            "oklch" => Some(Self::Oklch),
+            _ => None,
+        }
+    }
+
+    /// Name of a `color()` space in the conversion graph.
+    fn graph_name(name: &str) -> &str {
+        match name {
+            "srgb-linear" => "linear-srgb",
+            "display-p3-linear" => "linear-display-p3",
+            "xyz" | "xyz-d65" => "xyz",
+            name => name,
+        }
+    }
+
+    fn color(name: &str) -> Result<Self, CssError> {
+        if graph::convert(Self::graph_name(name), "oklab").is_some() {
+            Ok(Self::Color(name.to_string()))
+        } else {
+            Err(CssError::UnknownSpace(name.to_string()))
+        }
+    }
+
+    /// Keywords naming the channels in relative colors.
+    fn channels(&self) -> [&'static str; 3] {
+        match self {
+            Self::Rgb => ["r", "g", "b"],
+            Self::Hsl => ["h", "s", "l"],
+            Self::Hwb => ["h", "w", "b"],
+            Self::Lab | Self::Oklab => ["l", "a", "b"],
+            Self::Lch | Self::Oklch => ["l", "c", "h"],
+            Self::Color(name) if name.starts_with("xyz") => ["x", "y", "z"],
+            Self::Color(_) => ["r", "g", "b"],
+        }
+    }
+
+    /// Channel values at `100%`.
+    fn percent(&self) -> [f32; 3] {
+        match self {
+            Self::Rgb => [255.0; 3],
+            Self::Hsl | Self::Hwb => [1.0, 100.0, 100.0],
+            Self::Lab => [100.0, 125.0, 125.0],
+            Self::Lch => [100.0, 150.0, 1.0],
+            Self::Oklab => [1.0, 0.4, 0.4],
+            Self::Oklch => [1.0, 0.4, 1.0],
+            Self::Color(_) => [1.0; 3],
+        }
+    }
+
+    fn hue(&self) -> Option<usize> {
+        match self {
+            Self::Hsl | Self::Hwb => Some(0),
+            Self::Lch | Self::Oklch => Some(2),
+            _ => None,
+        }
+    }
+
+    /// Only sRGB can be written as hex, `rgb()`, `hsl()` and `hwb()`.
+    fn clamps(&self) -> bool {
+        matches!(self, Self::Rgb | Self::Hsl | Self::Hwb)
+    }
+
+    fn to_oklab(&self, [x, y, z]: [f32; 3]) -> Vec3 {
+        let srgb = |srgb: Vec3| graph::convert("srgb", "oklab").map(|e| e.embed(srgb));
+        match self {
+            Self::Rgb => srgb(vec3(x, y, z) / 255.0),
+            Self::Hsl => srgb(hsl_to_srgb(x, y / 100.0, z / 100.0)),
+            Self::Hwb => srgb(hwb_to_srgb(x, y / 100.0, z / 100.0)),
+            Self::Lab => Some(lab_to_oklab(vec3(x, y, z))),
+            Self::Lch => Some(lab_to_oklab(from_polar(vec3(x, y, z)))),
+            Self::Oklab => Some(vec3(x, y, z)),
+            Self::Oklch => Some(from_polar(vec3(x, y, z))),
+            Self::Color(name) => {
+                graph::convert(Self::graph_name(name), "oklab").map(|e| e.embed(vec3(x, y, z)))
+            }
+        }
+        .unwrap_or(vec3(0.0, 0.0, 0.0))
+    }
+
+    fn values(&self, oklab: Vec3) -> [f32; 3] {
+        let srgb = || {
+            let srgb = graph::convert("srgb", "oklab").map_or(oklab, |e| e.invert(oklab));
+            if self.clamps() {
+                srgb.map(|c| c.clamp(0.0, 1.0))
+            } else {
+                srgb
+            }
+        };
+        let pos = match self {
+            Self::Rgb => srgb() * 255.0,
+            Self::Hsl => {
+                let hsl = srgb_to_hsl(srgb());
+                vec3(hsl.x, hsl.y * 100.0, hsl.z * 100.0)
+            }
+            Self::Hwb => {
+                let hwb = srgb_to_hwb(srgb());
+                vec3(hwb.x, hwb.y * 100.0, hwb.z * 100.0)
+            }
+            Self::Lab => oklab_to_lab(oklab),
+            Self::Lch => to_polar(oklab_to_lab(oklab)),
+            Self::Oklab => oklab,
+            Self::Oklch => to_polar(oklab),
+            Self::Color(name) => {
+                graph::convert(Self::graph_name(name), "oklab").map_or(oklab, |e| e.invert(oklab))
+            }
+        };
+        [pos.x, pos.y, pos.z]
+    }
+}
+
+fn lab_to_oklab(lab: Vec3) -> Vec3 {
+    let xyz: Xyz<D50> = Xyz::from_color(Lab::<D50>::new(lab.x, lab.y, lab.z));
+    xyz_d50_to_oklab(vec3(xyz.x, xyz.y, xyz.z))
+}
+
+fn oklab_to_lab(oklab: Vec3) -> Vec3 {
+    let xyz = oklab_to_xyz_d50(oklab);
+    let lab = Lab::<D50>::from_color(Xyz::<D50>::new(xyz.x, xyz.y, xyz.z));
+    vec3(lab.l, lab.a, lab.b)
+}
+
+/// Bradford adapted from D50 to the D65 of Oklab.
+fn xyz_d50_to_oklab(xyz: Vec3) -> Vec3 {
+    graph::convert("xyz-d50", "oklab").map_or(xyz, |e| e.embed(xyz))
+}
+
+fn oklab_to_xyz_d50(oklab: Vec3) -> Vec3 {
+    graph::convert("xyz-d50", "oklab").map_or(oklab, |e| e.invert(oklab))
+}
+
+/// Lightness, chroma and hue in degrees of a Lab-like color.
+fn to_polar(lab: Vec3) -> Vec3 {
+    let chroma = vec2(lab.y, lab.z).magnitude();
+    let hue = lab.z.atan2(lab.y).to_degrees().rem_euclid(360.0);
+    vec3(lab.x, chroma, hue)
+}
+
+fn from_polar(lch: Vec3) -> Vec3 {
+    let hue = lch.z.to_radians();
+    vec3(
+        lch.x,
+        lch.y.max(0.0) * hue.cos(),
+        lch.y.max(0.0) * hue.sin(),
+    )
+}
+
+/// sRGB of a hue in degrees and saturation and lightness in `0..1`.
+fn hsl_to_srgb(hue: f32, saturation: f32, lightness: f32) -> Vec3 {
+    let a = saturation * lightness.min(1.0 - lightness);
+    let f = |n: f32| {
+        let k = (n + hue.rem_euclid(360.0) / 30.0) % 12.0;
+        lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    vec3(f(0.0), f(8.0), f(4.0))
+}
+
+fn srgb_to_hsl(srgb: Vec3) -> Vec3 {
+    let max = srgb.x.max(srgb.y).max(srgb.z);
+    let min = srgb.x.min(srgb.y).min(srgb.z);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+    if delta <= 0.0 {
+        return vec3(0.0, 0.0, lightness);
+    }
+    let saturation = if lightness == 0.0 || lightness == 1.0 {
+        0.0
+    } else {
+        (max - lightness) / lightness.min(1.0 - lightness)
+    };
+    vec3(srgb_hue(srgb, max, delta), saturation, lightness)
+}
+
+/// Hue in degrees of an sRGB color with largest channel `max`, `delta` above the smallest.
+fn srgb_hue(srgb: Vec3, max: f32, delta: f32) -> f32 {
+    let sextant = if max == srgb.x {
+        (srgb.y - srgb.z) / delta
+    } else if max == srgb.y {
+        (srgb.z - srgb.x) / delta + 2.0
+    } else {
+        (srgb.x - srgb.y) / delta + 4.0
+    };
+    (sextant * 60.0).rem_euclid(360.0)
+}
+
+fn hwb_to_srgb(hue: f32, whiteness: f32, blackness: f32) -> Vec3 {
+    if whiteness + blackness >= 1.0 {
+        let gray = whiteness / (whiteness + blackness);
+        return vec3(gray, gray, gray);
+    }
+    hsl_to_srgb(hue, 1.0, 0.5) * (1.0 - whiteness - blackness) + vec3(1.0, 1.0, 1.0) * whiteness
+}
+
+fn srgb_to_hwb(srgb: Vec3) -> Vec3 {
+    let max = srgb.x.max(srgb.y).max(srgb.z);
+    let min = srgb.x.min(srgb.y).min(srgb.z);
+    let hue = if max > min {
+        srgb_hue(srgb, max, max - min)
+    } else {
+        0.0
+    };
+    vec3(hue, min, 1.0 - max)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Percentage(f32),
+    Dimension(f32, String),
+    Ident(String),
+    Hash(String),
+    /// A name followed by `(`, empty for a bare parenthesis.
+    Function(String),
+    Close,
+    Comma,
+    Slash,
+    /// `+`, `-` or `*` between values.
+    Operator(char),
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, CssError> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let word = |i: &mut usize| {
+        let start = *i;
+        while *i < chars.len() && (chars[*i].is_alphanumeric() || "-_".contains(chars[*i])) {
+            *i += 1;
+        }
+        chars[start..*i].iter().collect::<String>()
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied().unwrap_or(' ');
+        let starts_number = c.is_ascii_digit()
+            || (c == '.' && next.is_ascii_digit())
+            || ("+-".contains(c) && (next.is_ascii_digit() || next == '.'));
+        if c.is_whitespace() {
+            i += 1;
+        } else if starts_number {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // An exponent, unless the `e` starts a unit
+            if i < chars.len() && "eE".contains(chars[i]) {
+                let sign = chars.get(i + 1).is_some_and(|c| "+-".contains(*c));
+                let digit = chars.get(i + 1 + sign as usize);
+                if digit.is_some_and(char::is_ascii_digit) {
+                    i += 1 + sign as usize;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let number: String = chars[start..i].iter().collect();
+            let number: f32 = number
+                .parse()
+                .map_err(|_| CssError::Syntax(format!("bad number {}", number)))?;
+            if chars.get(i) == Some(&'%') {
+                i += 1;
+                tokens.push(Token::Percentage(number));
+            } else if chars.get(i).is_some_and(|c| c.is_alphabetic()) {
+                tokens.push(Token::Dimension(number, word(&mut i).to_lowercase()));
+            } else {
+                tokens.push(Token::Number(number));
+            }
+        } else if c.is_alphabetic() || (c == '-' && next.is_alphabetic()) {
+            let name = word(&mut i).to_lowercase();
+            if chars.get(i) == Some(&'(') {
+                i += 1;
+                tokens.push(Token::Function(name));
+            } else {
+                tokens.push(Token::Ident(name));
+            }
+        } else {
+            i += 1;
+            tokens.push(match c {
+                '#' => Token::Hash(word(&mut i)),
+                '(' => Token::Function(String::new()),
+                ')' => Token::Close,
+                ',' => Token::Comma,
+                '/' => Token::Slash,
+                '+' | '-' | '*' => Token::Operator(c),
+                c => return syntax(format!("unexpected {}", c)),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// What a channel value is resolved against.
+struct Channel<'a> {
+    /// The value of `100%`.
+    percent: f32,
+    hue: bool,
+    /// Channel keywords of a relative color and their values.
+    keywords: &'a [(&'static str, f32)],
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn take(&mut self) -> Result<Token, CssError> {
+        let token = self.peek().cloned();
+        self.next += 1;
+        token.ok_or_else(|| CssError::Syntax("unexpected end".into()))
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn color(&mut self) -> Result<CssColor, CssError> {
+        match self.take()? {
+            Token::Hash(digits) => hex(&digits),
+            Token::Ident(name) if name == "transparent" => {
+                Ok(CssColor::new(vec3(0.0, 0.0, 0.0), 0.0))
+            }
+            Token::Ident(name) => match named(&name) {
+                Some(rgb) => {
+                    let channels = [rgb >> 16, rgb >> 8, rgb].map(|c| (c & 0xff) as f32);
+                    Ok(CssColor::new(CssSpace::Rgb.to_oklab(channels), 1.0))
+                }
+                None => syntax(format!("unknown color {}", name)),
+            },
+            Token::Function(name) if name == "color" => {
+                let origin = self.origin()?;
+                let space = match self.take()? {
+                    Token::Ident(space) => CssSpace::color(&space)?,
+                    token => return syntax(format!("expected a color space, found {:?}", token)),
+                };
+                self.components(space, origin)
+            }
+            Token::Function(name) => match CssSpace::from_function(&name) {
+                Some(space) => {
+                    let origin = self.origin()?;
+                    self.components(space, origin)
+                }
+                None => syntax(format!("unknown color function {}", name)),
+            },
+            token => syntax(format!("expected a color, found {:?}", token)),
+        }
+    }
+
+    /// The origin color of relative color syntax, after `from`.
+    fn origin(&mut self) -> Result<Option<CssColor>, CssError> {
+        if self.eat(&Token::Ident("from".into())) {
+            Ok(Some(self.color()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Three channels and an optional alpha up to the closing parenthesis, with or without the
+    /// commas of legacy syntax.
+    fn components(
+        &mut self,
+        space: CssSpace,
+        origin: Option<CssColor>,
+    ) -> Result<CssColor, CssError> {
+        let mut keywords = Vec::new();
+        if let Some(origin) = origin {
+            let values = space.values(origin.oklab);
+            keywords.extend(space.channels().into_iter().zip(values));
+            keywords.push(("alpha", origin.alpha));
+        }
+        let percent = space.percent();
+        let mut values = [0.0; 3];
+        for (i, value) in values.iter_mut().enumerate() {
+            if i > 0 {
+                self.eat(&Token::Comma);
+            }
+            let channel = Channel {
+                percent: percent[i],
+                hue: space.hue() == Some(i),
+                keywords: &keywords,
+            };
+            // Missing components act as zero
+            *value = self.value(&channel)?.unwrap_or(0.0);
+        }
+        let mut alpha = origin.map_or(1.0, |origin| origin.alpha);
+        if self.eat(&Token::Slash) || self.eat(&Token::Comma) {
+            let channel = Channel {
+                percent: 1.0,
+                hue: false,
+                keywords: &keywords,
+            };
+            alpha = self.value(&channel)?.unwrap_or(0.0).clamp(0.0, 1.0);
+        }
+        if !self.eat(&Token::Close) {
+            return syntax("expected )");
+        }
+        Ok(CssColor::new(space.to_oklab(values), alpha))
+    }
+
+    /// A channel value, `None` for `none`.
+    fn value(&mut self, channel: &Channel) -> Result<Option<f32>, CssError> {
+        if self.eat(&Token::Ident("none".into())) {
+            return Ok(None);
+        }
+        self.term(channel).map(Some)
+    }
+
+    fn sum(&mut self, channel: &Channel) -> Result<f32, CssError> {
+        let mut value = self.product(channel)?;
+        loop {
+            if self.eat(&Token::Operator('+')) {
+                value += self.product(channel)?;
+            } else if self.eat(&Token::Operator('-')) {
+                value -= self.product(channel)?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self, channel: &Channel) -> Result<f32, CssError> {
+        let mut value = self.term(channel)?;
+        loop {
+            if self.eat(&Token::Operator('*')) {
+                value *= self.term(channel)?;
+            } else if self.eat(&Token::Slash) {
+                value /= self.term(channel)?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// A single value. Arithmetic only happens inside `calc()` and parentheses, so `/` separates
+    /// alpha elsewhere.
+    fn term(&mut self, channel: &Channel) -> Result<f32, CssError> {
+        match self.take()? {
+            Token::Number(value) => Ok(value),
+            Token::Percentage(value) => Ok(value / 100.0 * channel.percent),
+            Token::Dimension(value, unit) if channel.hue => match unit.as_str() {
+                "deg" => Ok(value),
+                "rad" => Ok(value.to_degrees()),
+                "grad" => Ok(value * 0.9),
+                "turn" => Ok(value * 360.0),
+                unit => syntax(format!("unknown angle unit {}", unit)),
+            },
+            Token::Dimension(_, unit) => syntax(format!("unexpected unit {}", unit)),
+            Token::Ident(name) => match name.as_str() {
+                "pi" => Ok(PI),
+                "e" => Ok(std::f32::consts::E),
+                name => channel
+                    .keywords
+                    .iter()
+                    .find(|(keyword, _)| *keyword == name)
+                    .map(|&(_, value)| value)
+                    .ok_or_else(|| CssError::Syntax(format!("unknown keyword {}", name))),
+            },
+            Token::Function(name) if name.is_empty() || name == "calc" => {
+                let value = self.sum(channel)?;
+                if !self.eat(&Token::Close) {
+                    return syntax("expected )");
+                }
+                Ok(value)
+            }
+            token => syntax(format!("expected a number, found {:?}", token)),
+        }
+    }
+}
+
+/// The named colors of CSS Color 4, sorted by name.
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+/// A named color such as `rebeccapurple`, as `0xrrggbb`.
+fn named(name: &str) -> Option<u32> {
+    NAMED_COLORS
+        .binary_search_by_key(&name, |&(named, _)| named)
+        .ok()
+        .map(|i| NAMED_COLORS[i].1)
+}
+
+fn hex(digits: &str) -> Result<CssColor, CssError> {
+    let value = |digits: &str| {
+        u8::from_str_radix(digits, 16)
+            .map(|v| v as f32)
+            .map_err(|_| CssError::Syntax(format!("bad hex color #{}", digits)))
+    };
+    // Byte lengths and slices below only line up with characters for ASCII
+    if !digits.is_ascii() {
+        return syntax(format!("bad hex color #{}", digits));
+    }
+    let channels = match digits.len() {
+        3 | 4 => digits
+            .chars()
+            .map(|c| value(&c.to_string().repeat(2)))
+            .collect::<Result<Vec<_>, _>>()?,
+        6 | 8 => (0..digits.len() / 2)
+            .map(|i| value(&digits[i * 2..i * 2 + 2]))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return syntax(format!("bad hex color #{}", digits)),
+    };
+    let oklab = CssSpace::Rgb.to_oklab([channels[0], channels[1], channels[2]]);
+    let alpha = channels.get(3).map_or(1.0, |alpha| alpha / 255.0);
+    Ok(CssColor::new(oklab, alpha))
+}
+
+/// Reads a CSS Color 4 color: a name, hex, `rgb()`, `hsl()`, `hwb()`, `lab()`, `lch()`, `oklab()`,
+/// `oklch()` or `color()`, with `none` components, `calc()` and relative color syntax such as
+/// `oklch(from #0af l c calc(h + 180))`.
+pub fn parse(src: &str) -> Result<CssColor, CssError> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        next: 0,
+    };
+    let color = parser.color()?;
+    if parser.peek().is_some() {
+        return syntax("unexpected text after the color");
+    }
+    Ok(color)
+}
+
+/// How a color is written out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CssSyntax {
+    /// `#rrggbb`, or `#rrggbbaa` when translucent.
+    Hex,
+    Function(CssSpace),
+}
+
+impl CssSyntax {
+    /// `"hex"`, a color function name, or the name of a `color()` space.
+    pub fn from_name(name: &str) -> Result<Self, CssError> {
+        if name == "hex" {
+            return Ok(Self::Hex);
+        }
+        match CssSpace::from_function(name) {
+            Some(space) => Ok(Self::Function(space)),
+            None => CssSpace::color(name).map(Self::Function),
+        }
+    }
+}
+
+/// `value` rounded to `decimals`, without trailing zeros.
+fn number(value: f32, decimals: usize) -> String {
+    let text = format!("{:.*}", decimals, value);
+    let text = if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &text
+    };
+    match text {
+        "-0" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
+
+pub fn serialize(color: &CssColor, syntax: &CssSyntax) -> String {
+    let space = match syntax {
+        CssSyntax::Hex => {
+            let [r, g, b] = CssSpace::Rgb.values(color.oklab).map(|c| c.round() as u8);
+            let alpha = (color.alpha * 255.0).round() as u8;
+            return if alpha == 255 {
+                format!("#{:02x}{:02x}{:02x}", r, g, b)
+            } else {
+                format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, alpha)
+            };
+        }
+        CssSyntax::Function(space) => space,
+    };
+    let values = space.values(color.oklab);
+    let [x, y, z] = match space {
+        CssSpace::Rgb => values.map(|value| number(value, 0)),
+        CssSpace::Hsl | CssSpace::Hwb => [
+            number(values[0], 1),
+            format!("{}%", number(values[1], 1)),
+            format!("{}%", number(values[2], 1)),
+        ],
+        CssSpace::Lab | CssSpace::Lch => values.map(|value| number(value, 2)),
+        CssSpace::Oklab => values.map(|value| number(value, 4)),
+        CssSpace::Oklch => [
+            number(values[0], 4),
+            number(values[1], 4),
+            number(values[2], 2),
+        ],
+        CssSpace::Color(_) => values.map(|value| number(value, 4)),
+    };
+    let function = match space {
+        CssSpace::Rgb => "rgb(".to_string(),
+        CssSpace::Hsl => "hsl(".to_string(),
+        CssSpace::Hwb => "hwb(".to_string(),
+        CssSpace::Lab => "lab(".to_string(),
+        CssSpace::Lch => "lch(".to_string(),
+        CssSpace::Oklab => "oklab(".to_string(),
+        CssSpace::Oklch => "oklch(".to_string(),
+        CssSpace::Color(name) => format!("color({} ", name),
+    };
+    let alpha = if color.alpha < 1.0 {
+        format!(" / {}", number(color.alpha, 3))
+    } else {
+        String::new()
+    };
+    format!("{}{} {} {}{})", function, x, y, z, alpha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex() {
+        let short = parse("#0af").unwrap();
+        let long = parse("#00aaff").unwrap();
+        assert!((short.oklab - long.oklab).magnitude() < 1e-6);
+        assert_eq!(parse("#00aaff80").unwrap().alpha, 128.0 / 255.0);
+    }
+
+    #[test]
+    fn rejects_non_ascii_hex() {
+        for src in ["#aéaaa", "#éa", "#ééé", "#aaaaaé"] {
+            assert!(parse(src).is_err(), "{}", src);
+        }
+    }
+
+    fn assert_close(a: CssColor, b: CssColor) {
+        let error = (a.oklab - b.oklab).magnitude() + (a.alpha - b.alpha).abs();
+        assert!(error < 2e-3, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_same(a: &str, b: &str) {
+        let parsed = |src| parse(src).unwrap_or_else(|error| panic!("{}: {}", src, error));
+        let (a, b) = (parsed(a), parsed(b));
+        assert_close(a, b);
+    }
+
+    #[test]
+    fn parses_named_colors() {
+        assert_same("red", "#ff0000");
+        assert_same("RebeccaPurple", "#663399");
+        assert_same("grey", "gray");
+        assert_eq!(parse("transparent").unwrap().alpha, 0.0);
+        assert!(NAMED_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(matches!(parse("reddish"), Err(CssError::Syntax(_))));
+    }
+
+    #[test]
+    fn parses_legacy_and_modern_srgb_functions() {
+        let cases = [
+            ("rgb(255, 0, 0)", "#f00"),
+            ("rgb(255 0 0)", "#f00"),
+            ("rgba(255, 0, 0, 0.5)", "#ff000080"),
+            ("rgb(100% 0% 0% / 50%)", "#ff000080"),
+            ("hsl(120, 100%, 50%)", "#0f0"),
+            ("hsl(120 100% 50%)", "#0f0"),
+            ("hsla(240, 100%, 50%, 0.5)", "#0000ff80"),
+            ("hsl(240 100 50 / 0.5)", "#0000ff80"),
+            ("hwb(0 0% 0%)", "#f00"),
+            ("hwb(60 0% 0%)", "#ff0"),
+            ("hwb(0 50% 50%)", "#808080"),
+        ];
+        for (a, b) in cases {
+            assert_same(a, b);
+        }
+    }
+
+    #[test]
+    fn parses_lab_like_functions_with_percentages() {
+        let cases = [
+            ("lab(50% 25% -25%)", "lab(50 31.25 -31.25)"),
+            ("lch(50% 50% 30)", "lch(50 75 30)"),
+            ("oklab(50% 25% -25%)", "oklab(0.5 0.1 -0.1)"),
+            ("oklch(60% 50% 90)", "oklch(0.6 0.2 90)"),
+            ("oklch(62.8% 0.2577 29.23)", "red"),
+            ("lab(54.29 80.8 69.89)", "red"),
+        ];
+        for (a, b) in cases {
+            assert_same(a, b);
+        }
+        let oklab = parse("oklab(0.5 0.1 -0.1)").unwrap().oklab;
+        assert!((oklab - vec3(0.5, 0.1, -0.1)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn parses_none_angles_and_calc() {
+        let cases = [
+            ("oklch(0.7 0.1 none)", "oklch(0.7 0.1 0)"),
+            ("rgb(none 255 0)", "#0f0"),
+            ("hsl(0.5turn 100% 50%)", "hsl(180 100% 50%)"),
+            ("hsl(200grad 100% 50%)", "hsl(180 100% 50%)"),
+            ("hsl(3.14159265rad 100% 50%)", "hsl(180 100% 50%)"),
+            ("hsl(180deg 100% 50%)", "hsl(180 100% 50%)"),
+            (
+                "rgb(calc(200 + 55) calc(100% / 2) calc((10 - 10) / 2))",
+                "rgb(255 127.5 0)",
+            ),
+            ("oklch(0.7 0.1 calc(pi * 1rad))", "oklch(0.7 0.1 180)"),
+            ("rgb(255 0 0 / calc(1 / 4))", "#ff000040"),
+        ];
+        for (a, b) in cases {
+            assert_same(a, b);
+        }
+        assert!(parse("rgb(10px 0 0)").is_err());
+        assert!(parse("hsl(10parsec 100% 50%)").is_err());
+    }
+
+    #[test]
+    fn parses_relative_colors() {
+        let cases = [
+            ("rgb(from red r g b)", "red"),
+            ("rgb(from red b g r)", "blue"),
+            ("hsl(from red calc(h + 120) s l)", "lime"),
+            ("oklch(from #0af l c calc(h + 360))", "#0af"),
+            ("oklab(from red l a b / 0.5)", "#ff000080"),
+            ("rgb(from #ff000080 r g b / alpha)", "#ff000080"),
+            ("color(from red srgb r g b)", "red"),
+            (
+                "lch(from color(display-p3 1 0 0) l c h)",
+                "color(display-p3 1 0 0)",
+            ),
+        ];
+        for (a, b) in cases {
+            assert_same(a, b);
+        }
+        assert!(parse("rgb(from red x y z)").is_err());
+    }
+
+    #[test]
+    fn parses_every_predefined_color_space() {
+        let white = parse("white").unwrap();
+        for space in [
+            "srgb",
+            "srgb-linear",
+            "display-p3",
+            "rec2020",
+            "a98-rgb",
+            "prophoto-rgb",
+        ] {
+            let src = format!("color({} 1 1 1)", space);
+            assert_close(parse(&src).unwrap(), white);
+            let src = format!("color({} 0 0 0)", space);
+            assert!(parse(&src).unwrap().oklab.magnitude() < 1e-3, "{}", src);
+        }
+        assert_same("color(xyz 0.95047 1 1.08883)", "white");
+        assert_same("color(xyz-d65 0.95047 1 1.08883)", "white");
+        assert_same("color(xyz-d50 0.9643 1 0.8251)", "white");
+        assert_same("color(srgb 1 0 0)", "red");
+        assert_same(
+            "color(srgb-linear 0.2140 0.2140 0.2140)",
+            "rgb(128 128 128)",
+        );
+        // Values from the CSS Color 4 conversion code
+        assert_same("color(rec2020 0.79198 0.23098 0.07376)", "red");
+        assert_same("color(a98-rgb 0.85859 0 0)", "red");
+        assert_same("color(prophoto-rgb 0.70225 0.27572 0.10355)", "red");
+        assert_eq!(
+            parse("color(nowhere 1 1 1)"),
+            Err(CssError::UnknownSpace("nowhere".into()))
+        );
+    }
+
+    #[test]
+    fn serialized_colors_parse_back() {
+        let colors = [
+            CssColor::new(parse("#0af").unwrap().oklab, 1.0),
+            CssColor::new(parse("rebeccapurple").unwrap().oklab, 0.5),
+            CssColor::new(parse("oklch(0.45 0.1 300)").unwrap().oklab, 1.0),
+        ];
+        let names = [
+            "hex",
+            "rgb",
+            "hsl",
+            "hwb",
+            "lab",
+            "lch",
+            "oklab",
+            "oklch",
+            "srgb",
+            "srgb-linear",
+            "display-p3",
+            "rec2020",
+            "a98-rgb",
+            "prophoto-rgb",
+            "xyz",
+            "xyz-d50",
+            "xyz-d65",
+            "okhsv",
+        ];
+        for name in names {
+            let syntax = CssSyntax::from_name(name).unwrap();
+            for color in colors {
+                let src = serialize(&color, &syntax);
+                let parsed = parse(&src).unwrap_or_else(|error| panic!("{}: {}", src, error));
+                // Hex and rgb() round to whole 8-bit steps
+                let error = (parsed.oklab - color.oklab).magnitude();
+                assert!(error < 4e-3, "{} from {:?}", src, color);
+                assert!((parsed.alpha - color.alpha).abs() < 4e-3, "{}", src);
+            }
+        }
+    }
+}
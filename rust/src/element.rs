@@ -1,9 +1,15 @@
+use cgmath::SquareMatrix;
 use three_d::{ElementBuffer, Mat4, RenderStates, Vec3, VertexBuffer};
 
+use crate::mesh::{attribute::Attribute, export::ColoredMesh};
+
 pub mod coloraxis;
 pub mod colorchips;
 pub mod colorspace;
 pub mod embedswitcher;
+pub mod histogram;
+pub mod isolines;
+pub mod pointcloud;
 
 pub enum ModelGraph<'a> {
     Color(ColorModel<'a>),
@@ -11,6 +17,44 @@ pub enum ModelGraph<'a> {
     Vec(Vec<ModelGraph<'a>>),
 }
 
+/// Lighting applied to a model's colors. Anything but `None` changes the colors shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shading {
+    #[default]
+    None,
+    /// Soft light from above, darkening faces turned away by up to 30%.
+    Diffuse,
+    /// Darkens faces seen edge-on, outlining the silhouette.
+    Rim,
+}
+
+/// How a model's triangles are drawn. Lines and points are quads built by
+/// `CpuMesh::line_list`, `CpuMesh::line_strip` and `CpuMesh::points`, widened on screen.
+///
+/// GL line and point primitives would be simpler, but WebGL 2 implementations draw lines one
+/// pixel wide whatever `lineWidth` says and drop points whose center leaves the viewport. The
+/// quads keep their width, and stay wide enough to hit in the pick pass.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DrawMode {
+    #[default]
+    Triangles,
+    /// Needs `Attribute::LineEnd` and `Attribute::Corner`. `width` is in pixels.
+    Lines { width: f32 },
+    /// Needs `Attribute::Corner`. `size` is in pixels.
+    Points { size: f32 },
+}
+
+impl DrawMode {
+    /// The `primitive` and `width` uniforms of the color vertex shader.
+    pub fn uniforms(&self) -> (i32, f32) {
+        match *self {
+            Self::Triangles => (0, 0.0),
+            Self::Lines { width } => (1, width),
+            Self::Points { size } => (2, size),
+        }
+    }
+}
+
 pub struct ColorModel<'a> {
     pub positions: &'a VertexBuffer,
     pub embed: &'a VertexBuffer,
@@ -19,6 +63,36 @@ pub struct ColorModel<'a> {
     pub view: Mat4,
     pub model: Mat4,
     pub meta: Mat4,
+    /// Extra vertex channels, bound when the program reads them.
+    pub attributes: Vec<(Attribute, &'a VertexBuffer)>,
+    /// Needs an `Attribute::Normal` in `attributes`.
+    pub shading: Shading,
+    pub draw_mode: DrawMode,
+    /// Scales the alpha of the color program. Below 1 it needs blending in `render_states`.
+    pub opacity: f32,
+}
+
+impl<'a> ColorModel<'a> {
+    /// Plain triangles with identity matrices and no extra channels, for struct update syntax.
+    pub fn new(
+        positions: &'a VertexBuffer,
+        embed: &'a VertexBuffer,
+        indices: &'a ElementBuffer,
+    ) -> Self {
+        Self {
+            positions,
+            embed,
+            indices,
+            render_states: RenderStates::default(),
+            view: Mat4::identity(),
+            model: Mat4::identity(),
+            meta: Mat4::identity(),
+            attributes: Vec::new(),
+            shading: Shading::None,
+            draw_mode: DrawMode::Triangles,
+            opacity: 1.0,
+        }
+    }
 }
 
 pub struct TaggedColorModel<'a> {
@@ -32,8 +106,12 @@ pub trait ColorElement<T> {
     fn exited(&mut self) {}
     fn clicked(&mut self) {}
     fn update_state(&self, _state: &mut T) {}
-    fn model(&self) -> ModelGraph;
+    fn model(&self) -> ModelGraph<'_>;
     fn invert_space(&self, _pos: Vec3) -> Option<Vec3> {
         None
     }
+    /// The geometry drawn in space, with its colors, for saving it.
+    fn export(&self) -> Option<ColoredMesh> {
+        None
+    }
 }
@@ -2,7 +2,7 @@ use cgmath::{vec2, vec3, ElementWise, InnerSpace, SquareMatrix, Zero};
 use three_d::{degrees, radians, Context, Mat4, RenderStates, Vec3};
 
 use crate::{
-    embed::{AxisRepresentation, CylindricalEmbedding, StaticEmbedding},
+    embed::{AxisInfo, AxisRepresentation, CylindricalEmbedding, StaticEmbedding},
     input::InputState,
     mesh::Mesh,
     pre_embed::plane,
@@ -10,7 +10,7 @@ use crate::{
 
 use super::{ColorElement, ColorModel, ModelGraph};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Axis {
     X = 0,
     Y = 1,
@@ -28,6 +28,7 @@ pub struct ColorAxis {
     space: Vec3,
     hover: bool,
     representation: AxisRepresentation,
+    info: AxisInfo,
 }
 
 impl ColorAxis {
@@ -75,6 +76,7 @@ impl ColorAxis {
             space: Vec3::zero(),
             hover: false,
             representation: AxisRepresentation::Cylindrical,
+            info: AxisInfo::hue("turn", 1.0),
         }
     }
 
@@ -97,7 +99,7 @@ impl ColorAxis {
 }
 
 impl ColorElement<InputState> for ColorAxis {
-    fn model(&self) -> ModelGraph {
+    fn model(&self) -> ModelGraph<'_> {
         let pos = self.space;
         let flatten = Mat4::from_angle_z(degrees(-90.0));
         let (cursor_model, cursor_meta) = match self.representation {
@@ -135,18 +137,14 @@ impl ColorElement<InputState> for ColorAxis {
         };
         ModelGraph::Vec(vec![
             ModelGraph::Color(ColorModel {
-                positions: self.positions.vertex_buffer(),
-                embed: self.embed.vertex_buffer(),
-                indices: self.positions.element_buffer(),
-                render_states: RenderStates::default(),
                 view: self.view_matrix(),
-                model: Mat4::identity(),
-                meta: Mat4::identity(),
+                ..ColorModel::new(
+                    self.positions.vertex_buffer(),
+                    self.embed.vertex_buffer(),
+                    self.positions.element_buffer(),
+                )
             }),
             ModelGraph::Color(ColorModel {
-                positions: self.cursor_positions.vertex_buffer(),
-                embed: self.cursor_positions.vertex_buffer(),
-                indices: self.cursor_positions.element_buffer(),
                 render_states: RenderStates {
                     depth_test: three_d::DepthTest::Always,
                     ..Default::default()
@@ -154,15 +152,19 @@ impl ColorElement<InputState> for ColorAxis {
                 view: self.camera_view,
                 model: cursor_model,
                 meta: cursor_meta,
+                ..ColorModel::new(
+                    self.cursor_positions.vertex_buffer(),
+                    self.cursor_positions.vertex_buffer(),
+                    self.cursor_positions.element_buffer(),
+                )
             }),
             ModelGraph::Space(ColorModel {
-                positions: self.positions.vertex_buffer(),
-                embed: self.input.vertex_buffer(),
-                indices: self.positions.element_buffer(),
-                render_states: RenderStates::default(),
                 view: self.view_matrix(),
-                model: Mat4::identity(),
-                meta: Mat4::identity(),
+                ..ColorModel::new(
+                    self.positions.vertex_buffer(),
+                    self.input.vertex_buffer(),
+                    self.positions.element_buffer(),
+                )
             }),
         ])
     }
@@ -177,7 +179,13 @@ impl ColorElement<InputState> for ColorAxis {
 
     fn invert_space(&self, pos: Vec3) -> Option<Vec3> {
         Some(match self.axis {
-            Axis::X => vec3(pos.x + self.pos.x - 0.5, self.pos.y, self.pos.z),
+            // Periodic axes are shown centered on the selection, others from 0 to 1
+            Axis::X if self.info.periodic => vec3(
+                self.info.wrap(pos.x + self.pos.x - 0.5),
+                self.pos.y,
+                self.pos.z,
+            ),
+            Axis::X => vec3(pos.x, self.pos.y, self.pos.z),
             Axis::Y => vec3(self.pos.x, pos.y, self.pos.z),
             Axis::Z => vec3(self.pos.x, self.pos.y, pos.z),
         })
@@ -191,13 +199,15 @@ impl ColorElement<InputState> for ColorAxis {
         self.color = state.color_embedding.embed(pos);
         self.space = state.space_embedding.embed(pos);
         self.representation = state.space_embedding.axis_representation();
+        self.info = state.color_embedding.axes()[self.axis as usize];
         let scale = match self.axis {
             Axis::X | Axis::Z => vec3(1.0, 0.0, 1.0),
             Axis::Y => vec3(0.0, 1.0, 0.0),
         };
 
         let origin = match self.axis {
-            Axis::X => vec3(self.pos.x - 0.5, pos.y, pos.z),
+            Axis::X if self.info.periodic => vec3(self.pos.x - 0.5, pos.y, pos.z),
+            Axis::X => vec3(0.0, pos.y, pos.z),
             Axis::Y => vec3(pos.x, 0.0, pos.z),
             Axis::Z => vec3(pos.x, pos.y, 0.0),
         };
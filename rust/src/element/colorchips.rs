@@ -1,20 +1,14 @@
-use std::rc::Rc;
-
-use cgmath::{vec3, SquareMatrix, Zero};
+use cgmath::{vec3, Zero};
 use three_d::{Context, Mat4, Vec3};
 
-use crate::{
-    embed::{Embedding, IdentityEmbedding},
-    geometry::quad_mesh,
-    input::InputState,
-    mesh::Mesh,
-};
+use crate::{geometry::quad_mesh, input::InputState, mesh::Mesh};
 
 use super::{ColorElement, ColorModel, ModelGraph};
 
 pub struct ColorChips {
     positions: Mesh,
-    embed: Rc<dyn Embedding>,
+    /// Input position and Oklab color of every chip.
+    chips: Vec<(Vec3, Vec3)>,
     origin: Vec3,
     unit: Vec3,
     count: usize,
@@ -26,9 +20,9 @@ impl ColorChips {
     pub fn new(context: &Context, count: usize, size: f32) -> Self {
         Self {
             positions: Mesh::from_positions(context, quad_mesh()),
+            chips: Vec::new(),
             origin: Vec3::zero(),
             unit: vec3(0.5, -0.8, 0.0),
-            embed: Rc::new(IdentityEmbedding {}),
             count,
             size,
             hover: false,
@@ -41,7 +35,28 @@ impl ColorElement<InputState> for ColorChips {
         if !self.hover {
             self.origin = state.pos;
         }
-        self.embed = state.color_embedding.clone();
+        let embedding = &state.color_embedding;
+        // A palette replaces the steps from the selection
+        self.chips = if state.palette.is_empty() {
+            let axes = embedding.axes();
+            (0..self.count)
+                .map(|i| {
+                    let pos = self.origin + self.unit * (i as f32 / self.count as f32);
+                    let pos = vec3(
+                        axes[0].wrap(pos.x),
+                        axes[1].wrap(pos.y),
+                        axes[2].wrap(pos.z),
+                    );
+                    (pos, embedding.embed(pos))
+                })
+                .collect()
+        } else {
+            state
+                .palette
+                .iter()
+                .map(|&color| (embedding.invert(color), color))
+                .collect()
+        };
     }
 
     fn entered(&mut self) {
@@ -52,38 +67,29 @@ impl ColorElement<InputState> for ColorChips {
         self.hover = false;
     }
 
-    fn model(&self) -> ModelGraph {
+    fn model(&self) -> ModelGraph<'_> {
         let mut models = Vec::new();
-        for i in 0..self.count {
-            let pos = (self.origin + self.unit * (i as f32 / self.count as f32)).map(|x| {
-                if x < 0.0 || x > 1.0 {
-                    x.rem_euclid(1.0)
-                } else {
-                    x
-                }
-            });
-            let view = Mat4::from_translation(vec3(
-                1. - self.size,
-                1. - (self.size * (i + 1) as f32),
-                0.0,
-            )) * Mat4::from_scale(self.size);
+        let size = self.size.min(2.0 / self.chips.len().max(1) as f32);
+        for (i, &(pos, color)) in self.chips.iter().enumerate() {
+            let view = Mat4::from_translation(vec3(1. - size, 1. - (size * (i + 1) as f32), 0.0))
+                * Mat4::from_scale(size);
             models.push(ModelGraph::Color(ColorModel {
-                positions: self.positions.vertex_buffer(),
-                embed: self.positions.vertex_buffer(),
-                indices: self.positions.element_buffer(),
-                render_states: Default::default(),
                 view,
-                model: Mat4::identity(),
-                meta: Mat4::from_translation(self.embed.embed(pos)) * Mat4::from_scale(0.0),
+                meta: Mat4::from_translation(color) * Mat4::from_scale(0.0),
+                ..ColorModel::new(
+                    self.positions.vertex_buffer(),
+                    self.positions.vertex_buffer(),
+                    self.positions.element_buffer(),
+                )
             }));
             models.push(ModelGraph::Space(ColorModel {
-                positions: self.positions.vertex_buffer(),
-                embed: self.positions.vertex_buffer(),
-                indices: self.positions.element_buffer(),
-                render_states: Default::default(),
                 view,
-                model: Mat4::identity(),
                 meta: Mat4::from_translation(pos) * Mat4::from_scale(0.0),
+                ..ColorModel::new(
+                    self.positions.vertex_buffer(),
+                    self.positions.vertex_buffer(),
+                    self.positions.element_buffer(),
+                )
             }));
         }
         ModelGraph::Vec(models)
@@ -1,121 +1,311 @@
 use std::rc::Rc;
 
-use cgmath::{SquareMatrix, Zero};
-use three_d::{Context, Mat4, RenderStates, Vec3};
+use cgmath::{vec3, InnerSpace, SquareMatrix, Zero};
+use palette::{FromColor, Oklab, Srgb};
+use three_d::{Context, Mat3, Mat4, Vec3, VertexBuffer};
 
 use crate::{
-    embed::{ChunkRepresentation, Embedding, IdentityEmbedding},
+    embed::{
+        oklab_to_srgb, AffineEmbedding, ChainEmbedding, ChunkRepresentation, Embedding,
+        IdentityEmbedding, InverseEmbedding,
+    },
     input::InputState,
-    mesh::{CpuMesh, Mesh},
+    mesh::{
+        attribute::{Attribute, AttributeData},
+        clip::Plane,
+        export::ColoredMesh,
+        normals::Normals,
+        subdivide::Refinement,
+        CpuMesh, Mesh,
+    },
 };
 
-use super::{ColorElement, ColorModel, ModelGraph};
+use super::{ColorElement, ColorModel, ModelGraph, Shading};
+
+/// What the color of the solid shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpaceDisplay {
+    Color,
+    /// How far the color moves, in ΔEOK, per unit step of the input.
+    Distortion,
+}
+
+/// Root mean square of the color change per unit input step along each axis.
+fn distortion(jacobian: Mat3) -> f32 {
+    ((jacobian.x.magnitude2() + jacobian.y.magnitude2() + jacobian.z.magnitude2()) / 3.0).sqrt()
+}
+
+/// Oklab color of a heatmap going from blue at 1/4 ΔE per step to red at 4.
+fn heat(distortion: f32) -> Vec3 {
+    let stops: [Srgb; 3] = [
+        Srgb::new(0.1, 0.2, 0.8),
+        Srgb::new(0.9, 0.9, 0.2),
+        Srgb::new(0.9, 0.1, 0.1),
+    ];
+    let stops = stops.map(|srgb| {
+        let oklab = Oklab::from_color(srgb.into_linear::<f32>());
+        vec3(oklab.l, oklab.a, oklab.b)
+    });
+    let t = (distortion.max(1e-6).log2() / 4.0 + 0.5).clamp(0.0, 1.0) * 2.0;
+    if t < 1.0 {
+        stops[0] + (stops[1] - stops[0]) * t
+    } else {
+        stops[1] + (stops[2] - stops[1]) * (t - 1.0)
+    }
+}
 
 pub struct ColorSpace {
-    input: Mesh,
-    space: Mesh,
-    color: Mesh,
+    base: Rc<CpuMesh>,
+    refinement: Refinement,
+    clip: Option<Plane>,
+    /// Positions in space, with the `Input` position and Oklab `Color` of every vertex.
+    mesh: Mesh,
     chunk: Vec3,
+    /// Maps mesh positions to the input of the color embedding.
+    input_matrix: Mat4,
     view: Mat4,
     color_embedding: Rc<dyn Embedding<Vec3>>,
     space_embedding: Rc<dyn Embedding<Vec3>>,
     representation: ChunkRepresentation,
+    display: SpaceDisplay,
+    shading: Shading,
+    normals: Normals,
 }
 
 impl ColorSpace {
-    pub fn new(context: &Context, mesh: CpuMesh) -> Self {
-        let split = mesh.split_triangles();
-        let input = Mesh::new(context, split.clone());
-        let positions = Mesh::new(context, split.clone());
-        let embeded = Mesh::new(context, split.clone());
+    /// `mesh` is refined for the active embeddings whenever they change.
+    pub fn new(context: &Context, mesh: Rc<CpuMesh>, refinement: Refinement) -> Self {
+        let split = with_channels(mesh.split_triangles());
 
         ColorSpace {
-            space: positions,
-            input,
-            color: embeded,
+            base: mesh,
+            refinement,
+            clip: None,
+            mesh: Mesh::new(context, split).with_normals(Normals::Smooth),
             chunk: Vec3::zero(),
+            input_matrix: Mat4::identity(),
             view: Mat4::identity(),
             color_embedding: Rc::new(IdentityEmbedding {}),
             space_embedding: Rc::new(IdentityEmbedding {}),
             representation: ChunkRepresentation::Scale,
+            display: SpaceDisplay::Color,
+            shading: Shading::None,
+            normals: Normals::Smooth,
+        }
+    }
+}
+
+/// Starts the input and color channels at the positions, which are in input space.
+fn with_channels(mesh: CpuMesh) -> CpuMesh {
+    let input = AttributeData::Vec3(mesh.positions.clone());
+    mesh.with_attribute(Attribute::Input, input.clone())
+        .with_attribute(Attribute::Color, input)
+}
+
+impl ColorSpace {
+    fn buffer(&self, attribute: Attribute) -> &VertexBuffer {
+        self.mesh
+            .attribute_buffer(attribute)
+            .expect("color space meshes carry input and color")
+    }
+
+    /// Clips `mesh` and adds the cap, refined until its colors are smooth, back in input space.
+    ///
+    /// The plane cuts the solid where it is drawn, after the chunk scales it or opens the wedge.
+    /// Clamped solids are cut before clamping.
+    fn clip(
+        &self,
+        mesh: &CpuMesh,
+        state: &InputState,
+        plane: &Plane,
+        input_matrix: Mat4,
+    ) -> CpuMesh {
+        let space = state.space_embedding.clone();
+        let steps: Vec<Rc<dyn Embedding>> = match AffineEmbedding::new(input_matrix) {
+            // An empty chunk draws nothing to cut
+            None => return mesh.clone(),
+            Some(chunk) => match space.chunk_representation() {
+                ChunkRepresentation::Scale => vec![space, Rc::new(chunk)],
+                ChunkRepresentation::Wedge => vec![Rc::new(chunk), space],
+                ChunkRepresentation::Clamp => vec![space],
+            },
+        };
+        let drawn: Rc<dyn Embedding> = Rc::new(ChainEmbedding::new(steps));
+        let (mut clipped, cap) = mesh.clip(drawn.as_ref(), plane);
+        let inverse: Rc<dyn Embedding> = Rc::new(InverseEmbedding::new(drawn));
+        let cap_color = ChainEmbedding::new(vec![inverse.clone(), state.color_embedding.clone()]);
+        let mut cap = cap.adaptive_subdivide(&[&cap_color], &self.refinement);
+        cap.positions
+            .iter_mut()
+            .for_each(|pos| *pos = inverse.embed(*pos));
+        clipped.extend(&cap);
+        clipped
+    }
+
+    fn model_matrix(&self) -> Mat4 {
+        match self.representation {
+            ChunkRepresentation::Clamp | ChunkRepresentation::Wedge => Mat4::identity(),
+            ChunkRepresentation::Scale => {
+                Mat4::from_nonuniform_scale(self.chunk.x, self.chunk.y, self.chunk.z)
+            }
+        }
+    }
+}
+
+/// Fraction of the hues cut away by `ChunkRepresentation::Wedge`.
+pub(super) const WEDGE: f32 = 0.25;
+
+pub(super) fn input_matrix(representation: ChunkRepresentation, chunk: Vec3, hue: f32) -> Mat4 {
+    match representation {
+        ChunkRepresentation::Clamp => Mat4::identity(),
+        ChunkRepresentation::Scale => Mat4::from_nonuniform_scale(chunk.x, chunk.y, chunk.z),
+        // The mesh is squeezed into the remaining hues, so its x faces close the cut
+        ChunkRepresentation::Wedge => {
+            Mat4::from_translation(vec3(hue + WEDGE, 0.0, 0.0))
+                * Mat4::from_nonuniform_scale(1.0 - WEDGE, chunk.y, chunk.z)
         }
     }
 }
 
 impl ColorElement<InputState> for ColorSpace {
     fn update(&mut self, state: &InputState) {
-        use cgmath::ElementWise;
         self.view = state.camera.projection() * state.camera.view();
+        self.shading = state.shading;
+        if self.normals != state.normals {
+            self.normals = state.normals;
+            self.mesh.set_normals(state.normals);
+        }
         let representation = state.space_embedding.chunk_representation();
-        if state.chunk != self.chunk
-            || !Rc::<dyn Embedding>::ptr_eq(&self.color_embedding, &state.color_embedding)
-            || self.representation != representation
-        {
-            if representation == ChunkRepresentation::Clamp {
-                self.color
-                    .embed_from_positions(self.input.positions(), |pos| {
-                        state
-                            .color_embedding
-                            .embed(pos)
-                            .zip(self.chunk, |p, c| p.min(c))
-                    });
-                self.space
-                    .embed_from_positions(self.input.positions(), |pos| {
-                        state
-                            .space_embedding
-                            .embed(pos)
-                            .zip(self.chunk, |p, c| p.min(c))
-                    });
-            } else {
-                self.color
-                    .embed_from_positions(self.input.positions(), |pos| {
-                        state
-                            .color_embedding
-                            .embed(pos.mul_element_wise(state.chunk))
-                    });
+        let color_changed =
+            !Rc::<dyn Embedding>::ptr_eq(&self.color_embedding, &state.color_embedding);
+        let space_changed =
+            !Rc::<dyn Embedding>::ptr_eq(&self.space_embedding, &state.space_embedding);
+        let solid_changed = !Rc::ptr_eq(&self.base, &state.solid);
+        if solid_changed {
+            self.base = state.solid.clone();
+        }
+        // Picking on the solid moves `pos`, so the wedge follows the saved selection instead
+        let input_matrix = input_matrix(representation, state.chunk, state.saved_pos.x);
+        // The plane cuts the solid as drawn, so moving the chunk moves the cut
+        let clip_changed = self.clip != state.clip
+            || (state.clip.is_some()
+                && (self.input_matrix != input_matrix || self.representation != representation));
+        self.clip = state.clip;
+        // Refined meshes depend on both embeddings, and are filled with input positions
+        let reshaped = color_changed || space_changed || solid_changed || clip_changed;
+        if reshaped {
+            let mut refined = self.base.adaptive_subdivide(
+                &[
+                    state.space_embedding.as_ref(),
+                    state.color_embedding.as_ref(),
+                ],
+                &self.refinement,
+            );
+            if let Some(plane) = state.clip {
+                refined = self.clip(&refined, state, &plane, input_matrix);
             }
-            self.color_embedding = state.color_embedding.clone();
-            self.chunk = state.chunk;
-            self.representation = representation;
+            self.mesh.fill(&with_channels(refined.split_triangles()));
+        }
+        let recolored = reshaped
+            || color_changed
+            || state.chunk != self.chunk
+            || self.representation != representation
+            || self.display != state.space_display
+            || self.input_matrix != input_matrix;
+        if !recolored {
+            return;
         }
-        if !Rc::<dyn Embedding>::ptr_eq(&self.space_embedding, &state.space_embedding) {
-            self.space
-                .embed_from_positions(self.input.positions(), |pos| {
-                    state.space_embedding.embed(pos)
-                });
+        let input = self
+            .mesh
+            .attribute(Attribute::Input)
+            .and_then(AttributeData::as_vec3)
+            .cloned()
+            .unwrap_or_default();
+        if reshaped {
+            self.mesh
+                .embed_from_positions(&input, |pos| state.space_embedding.embed(pos));
             self.space_embedding = state.space_embedding.clone();
         }
+        let to_input = |pos: Vec3| (input_matrix * pos.extend(1.0)).truncate();
+        if representation == ChunkRepresentation::Wedge {
+            self.mesh
+                .embed_from_positions(&input, |pos| state.space_embedding.embed(to_input(pos)));
+        }
+        let color: Vec<Vec3> = if state.space_display == SpaceDisplay::Distortion {
+            input
+                .iter()
+                .map(|&pos| heat(distortion(state.color_embedding.jacobian(to_input(pos)))))
+                .collect()
+        } else if representation == ChunkRepresentation::Clamp {
+            let chunk = self.chunk;
+            self.mesh.embed_from_positions(&input, |pos| {
+                state.space_embedding.embed(pos).zip(chunk, |p, c| p.min(c))
+            });
+            input
+                .iter()
+                .map(|&pos| state.color_embedding.embed(pos).zip(chunk, |p, c| p.min(c)))
+                .collect()
+        } else {
+            input
+                .iter()
+                .map(|&pos| state.color_embedding.embed(to_input(pos)))
+                .collect()
+        };
+        self.mesh
+            .set_attribute(Attribute::Color, AttributeData::Vec3(color));
+        self.color_embedding = state.color_embedding.clone();
+        self.chunk = state.chunk;
+        self.input_matrix = input_matrix;
+        self.representation = representation;
+        self.display = state.space_display;
     }
 
-    fn model(&self) -> ModelGraph {
-        let model = match self.representation {
-            ChunkRepresentation::Clamp => Mat4::identity(),
-            ChunkRepresentation::Scale => {
-                Mat4::from_nonuniform_scale(self.chunk.x, self.chunk.y, self.chunk.z)
-            }
-        };
+    fn model(&self) -> ModelGraph<'_> {
+        let model = self.model_matrix();
         ModelGraph::Vec(vec![
             ModelGraph::Color(ColorModel {
-                positions: &self.space.vertex_buffer(),
-                embed: &self.color.vertex_buffer(),
-                indices: &self.space.element_buffer(),
-                render_states: RenderStates::default(),
                 view: self.view,
                 model,
-                meta: Mat4::identity(),
+                attributes: self.mesh.attribute_buffers(),
+                shading: self.shading,
+                ..ColorModel::new(
+                    self.mesh.vertex_buffer(),
+                    self.buffer(Attribute::Color),
+                    self.mesh.element_buffer(),
+                )
             }),
             ModelGraph::Space(ColorModel {
-                positions: &self.space.vertex_buffer(),
-                embed: &self.input.vertex_buffer(),
-                indices: &self.space.element_buffer(),
-                render_states: RenderStates::default(),
                 view: self.view,
                 model,
-                meta: model,
+                meta: self.input_matrix,
+                ..ColorModel::new(
+                    self.mesh.vertex_buffer(),
+                    self.buffer(Attribute::Input),
+                    self.mesh.element_buffer(),
+                )
             }),
         ])
     }
     fn invert_space(&self, pos: Vec3) -> Option<Vec3> {
-        Some(pos)
+        match self.representation {
+            ChunkRepresentation::Wedge => Some(vec3(pos.x.rem_euclid(1.0), pos.y, pos.z)),
+            _ => Some(pos),
+        }
+    }
+
+    fn export(&self) -> Option<ColoredMesh> {
+        let model = self.model_matrix();
+        // The drawn mesh is a triangle soup, welding closes it again for printing
+        let mut mesh = self.mesh.cpu_mesh.weld(1e-6);
+        mesh.positions
+            .iter_mut()
+            .for_each(|pos| *pos = (model * pos.extend(1.0)).truncate());
+        let colors = mesh
+            .attribute(Attribute::Color)?
+            .as_vec3()?
+            .iter()
+            .map(|&oklab| oklab_to_srgb(oklab))
+            .collect();
+        Some(ColoredMesh { mesh, colors })
     }
 }
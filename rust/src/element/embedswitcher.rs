@@ -1,14 +1,14 @@
 use std::rc::Rc;
 
-use cgmath::{vec3, SquareMatrix};
+use cgmath::vec3;
 use three_d::{Context, Mat4, RenderStates, Vec3};
 
 use crate::{
     element::coloraxis::Axis,
     embed::{
-        AxisRepresentation, ChunkRepresentation, ComposedEmbedding, CylindricalEmbedding,
-        Embedding, IdentityEmbedding, LinSrgbOklabEmbedding, OkhslEmbedding, OkhsvEmbedding,
-        SwapAxesEmbedding,
+        AffineEmbedding, AxisRepresentation, ChainEmbedding, ChunkRepresentation,
+        ComposedEmbedding, CylindricalEmbedding, Embedding, IdentityEmbedding,
+        LinSrgbOklabEmbedding, OkhslEmbedding, OkhsvEmbedding, SwapAxesEmbedding,
     },
     input::InputState,
     mesh::Mesh,
@@ -17,35 +17,100 @@ use crate::{
 
 use super::{ColorElement, ColorModel, ModelGraph};
 
+/// An embedding the switchers cycle through, with the identifier it is selected by.
+pub type NamedEmbedding = (&'static str, Rc<dyn Embedding<Vec3>>);
+
+fn color_list() -> Vec<NamedEmbedding> {
+    vec![
+        ("okhsv", Rc::new(OkhsvEmbedding {})),
+        ("okhsl", Rc::new(OkhslEmbedding {})),
+        ("linear-srgb", Rc::new(LinSrgbOklabEmbedding {})),
+        ("cylindrical", Rc::new(CylindricalEmbedding {})),
+        ("identity", Rc::new(IdentityEmbedding {})),
+    ]
+}
+
+fn space_list() -> Vec<NamedEmbedding> {
+    vec![
+        ("cylindrical", Rc::new(CylindricalEmbedding {})),
+        // Cylinder opened at the selected hue
+        (
+            "wedge",
+            Rc::new(ComposedEmbedding::new(
+                Box::new(IdentityEmbedding {}),
+                Box::new(CylindricalEmbedding {}),
+                AxisRepresentation::Cylindrical,
+                ChunkRepresentation::Wedge,
+            )),
+        ),
+        ("identity", Rc::new(IdentityEmbedding {})),
+        (
+            "linear-srgb",
+            Rc::new(ComposedEmbedding::new(
+                Box::new(SwapAxesEmbedding::new(Axis::X, Axis::Y)),
+                Box::new(LinSrgbOklabEmbedding {}),
+                AxisRepresentation::Cylindrical,
+                ChunkRepresentation::Clamp,
+            )),
+        ),
+        // Oklab with L up and a, b spread over the unit cube
+        (
+            "oklab",
+            Rc::new(ChainEmbedding::new(vec![
+                Rc::new(LinSrgbOklabEmbedding {}),
+                Rc::new(AffineEmbedding::normalize(
+                    vec3(0.0, -0.4, -0.4),
+                    vec3(1.0, 0.4, 0.4),
+                )),
+                Rc::new(AffineEmbedding::permutation([Axis::Y, Axis::X, Axis::Z])),
+            ])),
+        ),
+    ]
+}
+
+thread_local! {
+    // Shared so that embeddings selected by identifier are the ones the switchers hold
+    static COLOR_EMBEDDINGS: Vec<NamedEmbedding> = color_list();
+    static SPACE_EMBEDDINGS: Vec<NamedEmbedding> = space_list();
+}
+
+/// The color embeddings to switch between, first the default.
+pub fn color_embeddings() -> Vec<NamedEmbedding> {
+    COLOR_EMBEDDINGS.with(|embeddings| embeddings.clone())
+}
+
+/// The space embeddings to switch between, first the default.
+pub fn space_embeddings() -> Vec<NamedEmbedding> {
+    SPACE_EMBEDDINGS.with(|embeddings| embeddings.clone())
+}
+
+/// Identifier of `embedding` among `embeddings`.
+pub fn embedding_name(
+    embeddings: &[NamedEmbedding],
+    embedding: &Rc<dyn Embedding<Vec3>>,
+) -> Option<&'static str> {
+    embeddings
+        .iter()
+        .find(|(_, other)| Rc::<dyn Embedding>::ptr_eq(other, embedding))
+        .map(|&(name, _)| name)
+}
+
 pub struct EmbedSwitcher {
     pub quad: Mesh,
-    embeddings: Vec<Rc<dyn Embedding<Vec3>>>,
+    embeddings: Vec<NamedEmbedding>,
     index: usize,
+    /// The state holds an embedding from outside the list, kept until the next click.
+    custom: bool,
     color: bool,
     pos: f32,
 }
 
 impl EmbedSwitcher {
     pub fn new(context: &Context, switch_color: bool, pos: f32) -> Self {
-        let embeddings: Vec<Rc<dyn Embedding>> = if switch_color {
-            vec![
-                Rc::new(OkhsvEmbedding {}),
-                Rc::new(OkhslEmbedding {}),
-                Rc::new(LinSrgbOklabEmbedding {}),
-                Rc::new(CylindricalEmbedding {}),
-                Rc::new(IdentityEmbedding {}),
-            ]
+        let embeddings = if switch_color {
+            color_embeddings()
         } else {
-            vec![
-                Rc::new(CylindricalEmbedding {}),
-                Rc::new(IdentityEmbedding {}),
-                Rc::new(ComposedEmbedding::new(
-                    Box::new(SwapAxesEmbedding::new(Axis::X, Axis::Y)),
-                    Box::new(LinSrgbOklabEmbedding {}),
-                    AxisRepresentation::Cylindrical,
-                    ChunkRepresentation::Clamp,
-                )),
-            ]
+            space_embeddings()
         };
         let quad = Mesh::new(
             context,
@@ -56,56 +121,86 @@ impl EmbedSwitcher {
             color: switch_color,
             embeddings,
             index: 0,
+            custom: false,
             pos,
         }
     }
 }
 
 impl ColorElement<InputState> for EmbedSwitcher {
-    fn update(&mut self, _state: &InputState) {}
+    fn update(&mut self, state: &InputState) {
+        // Follows embeddings selected from outside, so a click steps on from them
+        let embedding = if self.color {
+            &state.color_embedding
+        } else {
+            &state.space_embedding
+        };
+        match self
+            .embeddings
+            .iter()
+            .position(|(_, other)| Rc::<dyn Embedding>::ptr_eq(other, embedding))
+        {
+            Some(index) => {
+                self.index = index;
+                self.custom = false;
+            }
+            None => self.custom = true,
+        }
+    }
 
     fn clicked(&mut self) {
-        self.index = (self.index + 1) % self.embeddings.len();
+        if self.custom {
+            self.custom = false;
+        } else {
+            self.index = (self.index + 1) % self.embeddings.len();
+        }
     }
 
     fn update_state(&self, state: &mut InputState) {
+        if self.custom {
+            return;
+        }
         if self.color {
-            if !Rc::<dyn Embedding>::ptr_eq(&state.color_embedding, &self.embeddings[self.index]) {
-                state.color_embedding = self.embeddings[self.index].clone();
+            if !Rc::<dyn Embedding>::ptr_eq(&state.color_embedding, &self.embeddings[self.index].1)
+            {
+                state.color_embedding = self.embeddings[self.index].1.clone();
             }
         } else {
-            if !Rc::<dyn Embedding>::ptr_eq(&state.space_embedding, &self.embeddings[self.index]) {
-                state.space_embedding = self.embeddings[self.index].clone();
+            if !Rc::<dyn Embedding>::ptr_eq(&state.space_embedding, &self.embeddings[self.index].1)
+            {
+                state.space_embedding = self.embeddings[self.index].1.clone();
             }
         }
     }
 
-    fn model(&self) -> ModelGraph {
+    fn model(&self) -> ModelGraph<'_> {
         let view = Mat4::from_translation(vec3(-1.0, 0.8 - self.pos, 0.0)) * Mat4::from_scale(0.2);
         ModelGraph::Vec(vec![
             ModelGraph::Color(ColorModel {
-                positions: &self.quad.vertex_buffer(),
-                embed: &self.quad.vertex_buffer(),
-                indices: &self.quad.element_buffer(),
                 render_states: RenderStates {
                     depth_test: three_d::DepthTest::Always,
                     ..Default::default()
                 },
-                view: view,
-                model: Mat4::identity(),
+                view,
                 meta: Mat4::from_scale(0.0),
+                ..ColorModel::new(
+                    self.quad.vertex_buffer(),
+                    self.quad.vertex_buffer(),
+                    self.quad.element_buffer(),
+                )
             }),
             ModelGraph::Space(ColorModel {
-                positions: &self.quad.vertex_buffer(),
-                embed: &self.quad.vertex_buffer(),
-                indices: &self.quad.element_buffer(),
                 render_states: RenderStates {
                     depth_test: three_d::DepthTest::Always,
                     ..Default::default()
                 },
-                view: view,
-                model: Mat4::identity(),
+                view,
                 meta: Mat4::from_scale(0.0),
+                ..ColorModel::new(
+                    self.quad.vertex_buffer(),
+                    self.quad.vertex_buffer(),
+                    self.quad.element_buffer(),
+                )
             }),
         ])
     }
@@ -0,0 +1,278 @@
+use std::rc::Rc;
+
+use cgmath::{vec3, InnerSpace, SquareMatrix};
+use three_d::{Blend, Context, DepthTest, Mat4, RenderStates, Vec3, VertexBuffer, WriteMask};
+
+use crate::{
+    embed::{Embedding, IdentityEmbedding},
+    input::InputState,
+    mesh::{
+        attribute::{Attribute, AttributeData},
+        CpuMesh, Mesh,
+    },
+    photo::Photo,
+};
+
+use super::{ColorElement, ColorModel, ModelGraph};
+
+/// How the histogram of the loaded image is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistogramDisplay {
+    /// A cube per occupied bin, larger the more pixels it holds.
+    Voxels,
+    /// Surfaces enclosing the denser regions.
+    Surfaces,
+}
+
+/// Bins along every input axis.
+const BINS: usize = 16;
+/// Most pixels read from a photo.
+const SAMPLES: usize = 100_000;
+/// Densities, relative to the densest bin, enclosed by the surfaces, with their opacities.
+const LEVELS: [(f32, f32); 2] = [(0.05, 0.25), (0.3, 0.5)];
+const VOXEL_OPACITY: f32 = 0.35;
+
+/// Pixel counts of a photo binned in the input space of an embedding, `x` fastest.
+fn bin(photo: &Photo, embedding: &dyn Embedding<Vec3>) -> Vec<f32> {
+    let axes = embedding.axes();
+    let mut counts = vec![0.0; BINS * BINS * BINS];
+    for color in photo.sample(SAMPLES) {
+        let pos = embedding.invert(color);
+        let [x, y, z] = [0, 1, 2].map(|i| {
+            let value = axes[i].wrap(pos[i]);
+            ((value * BINS as f32) as usize).min(BINS - 1)
+        });
+        counts[(z * BINS + y) * BINS + x] += 1.0;
+    }
+    counts
+}
+
+/// Cubes around the centers of occupied bins, in input space.
+fn voxels(counts: &[f32]) -> CpuMesh {
+    let densest = counts.iter().copied().fold(0.0, f32::max);
+    let cube = three_d::CpuMesh::cube().positions.to_f32();
+    let mut positions = Vec::new();
+    for (i, &count) in counts.iter().enumerate() {
+        if count == 0.0 {
+            continue;
+        }
+        let (x, y, z) = (i % BINS, i / BINS % BINS, i / (BINS * BINS));
+        let center = vec3(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5) / BINS as f32;
+        let half = 0.5 / BINS as f32 * (count / densest).cbrt();
+        positions.extend(cube.iter().map(|&corner| center + corner * half));
+    }
+    CpuMesh::new(positions, None)
+}
+
+/// Counts averaged with their neighbours, with a ring of empty or wrapped bins around them so
+/// the surfaces close. Returns the padded grid, `x` fastest.
+fn smooth(counts: &[f32], periodic: bool) -> Vec<f32> {
+    const PADDED: usize = BINS + 2;
+    let count = |x: isize, y: isize, z: isize| {
+        let x = if periodic {
+            x.rem_euclid(BINS as isize)
+        } else {
+            x
+        };
+        let inside = |v: isize| (0..BINS as isize).contains(&v);
+        if inside(x) && inside(y) && inside(z) {
+            counts[(z as usize * BINS + y as usize) * BINS + x as usize]
+        } else {
+            0.0
+        }
+    };
+    let mut grid = vec![0.0; PADDED * PADDED * PADDED];
+    for (i, value) in grid.iter_mut().enumerate() {
+        let (x, y, z) = (i % PADDED, i / PADDED % PADDED, i / (PADDED * PADDED));
+        let (x, y, z) = (x as isize - 1, y as isize - 1, z as isize - 1);
+        let mut sum = 0.0;
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    sum += count(x + dx, y + dy, z + dz);
+                }
+            }
+        }
+        *value = sum / 27.0;
+    }
+    grid
+}
+
+/// Tetrahedra splitting a cube along its diagonal, as corners numbered `x + 2y + 4z`.
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 3, 2, 7],
+    [0, 2, 6, 7],
+    [0, 6, 4, 7],
+    [0, 4, 5, 7],
+    [0, 5, 1, 7],
+];
+
+/// The surface where the padded `grid` crosses `level`, by marching tetrahedra, in input space
+/// and facing away from the denser side.
+fn surface(grid: &[f32], level: f32) -> CpuMesh {
+    const PADDED: usize = BINS + 2;
+    let mut positions = Vec::new();
+    let mut push = |mut triangle: [Vec3; 3], outward: Vec3| {
+        let normal = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]);
+        if normal.dot(outward) < 0.0 {
+            triangle.swap(1, 2);
+        }
+        positions.extend(triangle);
+    };
+    for z in 0..PADDED - 1 {
+        for y in 0..PADDED - 1 {
+            for x in 0..PADDED - 1 {
+                let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|c| {
+                    let (cx, cy, cz) = (x + (c & 1), y + (c >> 1 & 1), z + (c >> 2 & 1));
+                    // Padded bin centers, the first and last ones just outside `0..1`
+                    let pos = vec3(cx as f32 - 0.5, cy as f32 - 0.5, cz as f32 - 0.5);
+                    (pos / BINS as f32, grid[(cz * PADDED + cy) * PADDED + cx])
+                });
+                for tetrahedron in TETRAHEDRA {
+                    let corners = tetrahedron.map(|c| corners[c]);
+                    let (inside, outside): (Vec<_>, Vec<_>) =
+                        corners.iter().partition(|(_, value)| *value >= level);
+                    if inside.is_empty() || outside.is_empty() {
+                        continue;
+                    }
+                    let cut = |(a, va): (Vec3, f32), (b, vb): (Vec3, f32)| {
+                        a + (b - a) * ((va - level) / (va - vb))
+                    };
+                    let mean = |corners: &[&(Vec3, f32)]| {
+                        corners
+                            .iter()
+                            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, c| sum + c.0)
+                            / corners.len() as f32
+                    };
+                    let outward = mean(&outside) - mean(&inside);
+                    match (inside.as_slice(), outside.as_slice()) {
+                        ([&a], [&b, &c, &d]) | ([&b, &c, &d], [&a]) => {
+                            push([cut(a, b), cut(a, c), cut(a, d)], outward);
+                        }
+                        ([&a, &b], [&c, &d]) => {
+                            let quad = [cut(a, c), cut(a, d), cut(b, d), cut(b, c)];
+                            push([quad[0], quad[1], quad[2]], outward);
+                            push([quad[0], quad[2], quad[3]], outward);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    CpuMesh::new(positions, None)
+}
+
+/// Where the colors of a photo fall in the input space of the color solid.
+pub struct Histogram {
+    photo: Option<Rc<Photo>>,
+    display: Option<HistogramDisplay>,
+    /// Positions in space, with the Oklab `Color` of every vertex, and their opacities.
+    meshes: Vec<(Mesh, f32)>,
+    context: Context,
+    view: Mat4,
+    color_embedding: Rc<dyn Embedding<Vec3>>,
+    space_embedding: Rc<dyn Embedding<Vec3>>,
+}
+
+impl Histogram {
+    pub fn new(context: &Context) -> Self {
+        Self {
+            photo: None,
+            display: None,
+            meshes: Vec::new(),
+            context: context.clone(),
+            view: Mat4::identity(),
+            color_embedding: Rc::new(IdentityEmbedding {}),
+            space_embedding: Rc::new(IdentityEmbedding {}),
+        }
+    }
+
+    /// Moves `mesh` from input space into space, coloring it on the way.
+    fn place(&self, mesh: CpuMesh) -> Mesh {
+        let colors = mesh
+            .positions
+            .iter()
+            .map(|&pos| self.color_embedding.embed(pos))
+            .collect();
+        let mut mesh = mesh.with_attribute(Attribute::Color, AttributeData::Vec3(colors));
+        mesh.positions
+            .iter_mut()
+            .for_each(|pos| *pos = self.space_embedding.embed(*pos));
+        Mesh::new(&self.context, mesh)
+    }
+
+    fn buffer(mesh: &Mesh) -> &VertexBuffer {
+        mesh.attribute_buffer(Attribute::Color)
+            .expect("histograms carry their color")
+    }
+}
+
+impl ColorElement<InputState> for Histogram {
+    fn update(&mut self, state: &InputState) {
+        self.view = state.camera.projection() * state.camera.view();
+        let photo_changed = match (&self.photo, &state.photo) {
+            (Some(photo), Some(other)) => !Rc::ptr_eq(photo, other),
+            (None, None) => false,
+            _ => true,
+        };
+        let changed = photo_changed
+            || self.display != state.histogram
+            || !Rc::<dyn Embedding>::ptr_eq(&self.color_embedding, &state.color_embedding)
+            || !Rc::<dyn Embedding>::ptr_eq(&self.space_embedding, &state.space_embedding);
+        if !changed {
+            return;
+        }
+        self.photo = state.photo.clone();
+        self.display = state.histogram;
+        self.color_embedding = state.color_embedding.clone();
+        self.space_embedding = state.space_embedding.clone();
+        self.meshes.clear();
+        let photo = match (&self.photo, self.display) {
+            (Some(photo), Some(_)) => photo.clone(),
+            _ => return,
+        };
+        let counts = bin(&photo, self.color_embedding.as_ref());
+        self.meshes = match self.display {
+            Some(HistogramDisplay::Voxels) => vec![(self.place(voxels(&counts)), VOXEL_OPACITY)],
+            Some(HistogramDisplay::Surfaces) => {
+                let grid = smooth(&counts, self.color_embedding.axes()[0].periodic);
+                let densest = grid.iter().copied().fold(0.0, f32::max);
+                LEVELS
+                    .iter()
+                    .map(|&(level, opacity)| (self.place(surface(&grid, level * densest)), opacity))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        self.meshes.retain(|(mesh, _)| !mesh.positions().is_empty());
+    }
+
+    fn model(&self) -> ModelGraph<'_> {
+        // Drawn through the solid and each other, so the inside of the solid shows
+        let render_states = RenderStates {
+            blend: Blend::TRANSPARENCY,
+            depth_test: DepthTest::Always,
+            write_mask: WriteMask::COLOR,
+            ..Default::default()
+        };
+        ModelGraph::Vec(
+            self.meshes
+                .iter()
+                .map(|(mesh, opacity)| {
+                    ModelGraph::Color(ColorModel {
+                        render_states,
+                        view: self.view,
+                        opacity: *opacity,
+                        ..ColorModel::new(
+                            mesh.vertex_buffer(),
+                            Self::buffer(mesh),
+                            mesh.element_buffer(),
+                        )
+                    })
+                })
+                .collect(),
+        )
+    }
+}
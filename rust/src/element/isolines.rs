@@ -0,0 +1,375 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::{vec3, SquareMatrix};
+use three_d::{Context, Mat4, Vec3};
+
+use crate::{
+    embed::{ChunkRepresentation, Embedding},
+    input::InputState,
+    mesh::{
+        attribute::{Attribute, AttributeData},
+        clip::Plane,
+        subdivide::Refinement,
+        CpuMesh, Mesh,
+    },
+};
+
+use super::{
+    coloraxis::Axis, colorspace::input_matrix, ColorElement, ColorModel, DrawMode, ModelGraph,
+};
+
+/// The value the lines of an `IsoLines` keep constant.
+#[derive(Clone)]
+pub enum IsoSource {
+    /// An axis of the input space, e.g. the hue of an Okhsv solid.
+    Input(Axis),
+    /// An axis of the Oklab color.
+    Color(Axis),
+    /// The Oklab chroma, the distance of the color from the gray axis.
+    Chroma,
+    /// An axis of another embedding, found by inverting it at the color.
+    Embedding(Rc<dyn Embedding<Vec3>>, Axis),
+}
+
+impl IsoSource {
+    fn value(&self, color_embedding: &dyn Embedding<Vec3>, input: Vec3) -> f32 {
+        match self {
+            Self::Input(axis) => input[*axis as usize],
+            Self::Color(axis) => color_embedding.embed(input)[*axis as usize],
+            Self::Chroma => {
+                let color = color_embedding.embed(input);
+                color.y.hypot(color.z)
+            }
+            Self::Embedding(embedding, axis) => {
+                embedding.invert(color_embedding.embed(input))[*axis as usize]
+            }
+        }
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Input(a), Self::Input(b)) | (Self::Color(a), Self::Color(b)) => a == b,
+            (Self::Chroma, Self::Chroma) => true,
+            (Self::Embedding(embedding, a), Self::Embedding(other, b)) => {
+                Rc::<dyn Embedding>::ptr_eq(embedding, other) && a == b
+            }
+            _ => false,
+        }
+    }
+
+    fn periodic(&self, color_embedding: &dyn Embedding<Vec3>) -> bool {
+        match self {
+            Self::Input(axis) => color_embedding.axes()[*axis as usize].periodic,
+            Self::Color(_) | Self::Chroma => false,
+            Self::Embedding(embedding, axis) => embedding.axes()[*axis as usize].periodic,
+        }
+    }
+}
+
+/// Width of a line, in pixels.
+const WIDTH: f32 = 1.5;
+
+/// The solid and the color and space embeddings a refinement was made for.
+type RefinedFor = (
+    Rc<CpuMesh>,
+    Rc<dyn Embedding<Vec3>>,
+    Rc<dyn Embedding<Vec3>>,
+);
+
+/// The solid refined for the active embeddings, in input space. The `IsoLines` of a scene
+/// share one, so the solid is subdivided once for all of them.
+#[derive(Default)]
+pub struct RefinedSolid {
+    refinement: Refinement,
+    refined: Option<(RefinedFor, Rc<CpuMesh>)>,
+}
+
+impl RefinedSolid {
+    pub fn shared() -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self::default()))
+    }
+
+    /// The refined solid of `state`, subdivided again only when it or an embedding changed.
+    fn get(&mut self, state: &InputState) -> Rc<CpuMesh> {
+        if let Some(((solid, color, space), refined)) = &self.refined {
+            if Rc::ptr_eq(solid, &state.solid)
+                && Rc::<dyn Embedding>::ptr_eq(color, &state.color_embedding)
+                && Rc::<dyn Embedding>::ptr_eq(space, &state.space_embedding)
+            {
+                return refined.clone();
+            }
+        }
+        let refined = Rc::new(state.solid.adaptive_subdivide(
+            &[
+                state.space_embedding.as_ref(),
+                state.color_embedding.as_ref(),
+            ],
+            &self.refinement,
+        ));
+        let key = (
+            state.solid.clone(),
+            state.color_embedding.clone(),
+            state.space_embedding.clone(),
+        );
+        self.refined = Some((key, refined.clone()));
+        refined
+    }
+}
+
+/// Contour lines on the color solid where `source` is a multiple of `spacing`.
+pub struct IsoLines {
+    source: IsoSource,
+    spacing: f32,
+    /// Follows `InputState::extra_iso_lines` instead of keeping `source` and `spacing`.
+    extra: bool,
+    shared: Rc<RefCell<RefinedSolid>>,
+    /// The shared refined solid the lines were last traced on, while they are shown.
+    refined: Option<Rc<CpuMesh>>,
+    /// Positions in space, with the Oklab `Color` of every vertex.
+    mesh: Mesh,
+    enabled: bool,
+    clip: Option<Plane>,
+    input_matrix: Mat4,
+    model: Mat4,
+    view: Mat4,
+}
+
+impl IsoLines {
+    pub fn new(
+        context: &Context,
+        shared: Rc<RefCell<RefinedSolid>>,
+        source: IsoSource,
+        spacing: f32,
+    ) -> Self {
+        let empty = CpuMesh::new(Vec::new(), None)
+            .with_attribute(Attribute::Color, AttributeData::Vec3(Vec::new()));
+        Self {
+            source,
+            spacing,
+            extra: false,
+            shared,
+            refined: None,
+            mesh: Mesh::new(context, empty),
+            enabled: false,
+            clip: None,
+            input_matrix: Mat4::identity(),
+            model: Mat4::identity(),
+            view: Mat4::identity(),
+        }
+    }
+
+    /// The lines set by `InputState::extra_iso_lines`, hidden while it is `None`.
+    pub fn extra(context: &Context, shared: Rc<RefCell<RefinedSolid>>) -> Self {
+        Self {
+            extra: true,
+            ..Self::new(context, shared, IsoSource::Input(Axis::X), 1.0)
+        }
+    }
+
+    /// The contour segments of `refined`, as pairs of input space positions.
+    fn segments(
+        &self,
+        refined: &CpuMesh,
+        state: &InputState,
+        to_input: &dyn Fn(Vec3) -> Vec3,
+    ) -> Vec<[Vec3; 2]> {
+        let color_embedding = state.color_embedding.as_ref();
+        let periodic = self.source.periodic(color_embedding);
+        let values: Vec<f32> = refined
+            .positions
+            .iter()
+            .map(|&pos| self.source.value(color_embedding, to_input(pos)))
+            .collect();
+
+        let mut segments = Vec::new();
+        for triangle in refined.indices.chunks(3) {
+            let corners = [0, 1, 2].map(|i| refined.positions[triangle[i] as usize]);
+            let mut f = [0, 1, 2].map(|i| values[triangle[i] as usize]);
+            if periodic {
+                // Keep the corners on the same turn as the first one
+                for i in 1..3 {
+                    f[i] -= (f[i] - f[0]).round();
+                }
+            }
+            let low = f.iter().copied().fold(f32::INFINITY, f32::min);
+            let high = f.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let first = (low / self.spacing).ceil() as i32;
+            let last = (high / self.spacing).floor() as i32;
+            for level in first..=last {
+                let level = level as f32 * self.spacing;
+                let cuts: Vec<Vec3> = (0..3)
+                    .filter_map(|i| {
+                        let j = (i + 1) % 3;
+                        if (f[i] < level) == (f[j] < level) {
+                            return None;
+                        }
+                        let t = (level - f[i]) / (f[j] - f[i]);
+                        Some(corners[i] * (1.0 - t) + corners[j] * t)
+                    })
+                    .collect();
+                if let [a, b] = cuts[..] {
+                    segments.push([a, b]);
+                }
+            }
+        }
+        segments
+    }
+}
+
+/// Lighter lines on dark colors and darker lines on light ones, keeping the hue.
+fn contrast(oklab: Vec3) -> Vec3 {
+    let l = if oklab.x > 0.5 {
+        oklab.x - 0.35
+    } else {
+        oklab.x + 0.35
+    };
+    vec3(l, oklab.y, oklab.z)
+}
+
+/// The segments, which are in space, as quads for `DrawMode::Lines`.
+fn lines(segments: &[([Vec3; 2], Vec3)]) -> CpuMesh {
+    let positions = segments.iter().flat_map(|(ends, _)| *ends).collect();
+    let colors = segments.iter().flat_map(|&(_, color)| [color; 2]).collect();
+    CpuMesh::new(positions, None)
+        .with_attribute(Attribute::Color, AttributeData::Vec3(colors))
+        .line_list()
+}
+
+impl ColorElement<InputState> for IsoLines {
+    fn update(&mut self, state: &InputState) {
+        self.view = state.camera.projection() * state.camera.view();
+        let representation = state.space_embedding.chunk_representation();
+        let input_matrix = input_matrix(representation, state.chunk, state.saved_pos.x);
+        let model = match representation {
+            ChunkRepresentation::Scale => {
+                Mat4::from_nonuniform_scale(state.chunk.x, state.chunk.y, state.chunk.z)
+            }
+            _ => Mat4::identity(),
+        };
+        let (enabled, source_changed) = match (self.extra, &state.extra_iso_lines) {
+            (false, _) => (state.iso_lines, false),
+            (true, None) => (false, false),
+            (true, Some((source, spacing))) => {
+                let changed = !self.source.same(source) || self.spacing != *spacing;
+                self.source = source.clone();
+                self.spacing = *spacing;
+                (true, changed)
+            }
+        };
+        // Hidden lines leave the solid unrefined
+        let refined = enabled.then(|| self.shared.borrow_mut().get(state));
+        let reshaped = match (&self.refined, &refined) {
+            (Some(old), Some(new)) => !Rc::ptr_eq(old, new),
+            (None, None) => false,
+            _ => true,
+        };
+        let changed = reshaped
+            || source_changed
+            || self.enabled != enabled
+            || self.clip != state.clip
+            || self.input_matrix != input_matrix
+            || self.model != model;
+        if !changed {
+            return;
+        }
+        self.enabled = enabled;
+        self.refined = refined;
+        self.clip = state.clip;
+        self.input_matrix = input_matrix;
+        self.model = model;
+        let refined = match &self.refined {
+            Some(refined) => refined,
+            None => return,
+        };
+
+        let to_input = |pos: Vec3| (input_matrix * pos.extend(1.0)).truncate();
+        // Placed on the solid the same way `ColorSpace` places it
+        let place = |pos: Vec3| match representation {
+            ChunkRepresentation::Wedge => state.space_embedding.embed(to_input(pos)),
+            ChunkRepresentation::Clamp => state
+                .space_embedding
+                .embed(pos)
+                .zip(state.chunk, |p, c| p.min(c)),
+            ChunkRepresentation::Scale => state.space_embedding.embed(pos),
+        };
+        let kept = |pos: Vec3| match &state.clip {
+            Some(plane) => plane.distance(state.space_embedding.embed(pos)) >= 0.0,
+            None => true,
+        };
+        let segments: Vec<([Vec3; 2], Vec3)> = self
+            .segments(refined, state, &to_input)
+            .into_iter()
+            .filter(|[a, b]| kept(*a) && kept(*b))
+            .map(|[a, b]| {
+                let color = contrast(state.color_embedding.embed(to_input((a + b) / 2.0)));
+                ([place(a), place(b)], color)
+            })
+            .collect();
+        self.mesh.fill(&lines(&segments));
+    }
+
+    fn model(&self) -> ModelGraph<'_> {
+        if !self.enabled || self.mesh.positions().is_empty() {
+            return ModelGraph::Vec(Vec::new());
+        }
+        ModelGraph::Color(ColorModel {
+            view: self.view,
+            model: self.model,
+            attributes: self.mesh.attribute_buffers(),
+            draw_mode: DrawMode::Lines { width: WIDTH },
+            ..ColorModel::new(
+                self.mesh.vertex_buffer(),
+                self.mesh
+                    .attribute_buffer(Attribute::Color)
+                    .expect("iso-lines carry their color"),
+                self.mesh.element_buffer(),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use three_d::{degrees, Camera, Viewport};
+
+    use super::*;
+    use crate::{embed::IdentityEmbedding, pre_embed::Primitive};
+
+    fn state() -> InputState {
+        let camera = Camera::new_perspective(
+            Viewport::new_at_origo(1, 1),
+            vec3(0.0, 2.0, 4.0),
+            vec3(0.0, 0.5, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            degrees(45.0),
+            0.1,
+            10.0,
+        );
+        InputState::new(vec3(1.0, 1.0, 1.0), camera)
+    }
+
+    #[test]
+    fn refines_the_solid_once() {
+        let mut state = state();
+        let mut shared = RefinedSolid::default();
+        let first = shared.get(&state);
+        assert!(first.indices.len() > state.solid.indices.len());
+        assert!(Rc::ptr_eq(&first, &shared.get(&state)));
+
+        state.solid = Rc::new(Primitive::Cylinder.mesh());
+        let second = shared.get(&state);
+        assert!(!Rc::ptr_eq(&first, &second));
+        state.color_embedding = Rc::new(IdentityEmbedding {});
+        assert!(!Rc::ptr_eq(&second, &shared.get(&state)));
+    }
+
+    #[test]
+    fn measures_chroma() {
+        let oklab = IdentityEmbedding {};
+        assert_eq!(IsoSource::Chroma.value(&oklab, vec3(0.5, 0.3, -0.4)), 0.5);
+        assert_eq!(IsoSource::Chroma.value(&oklab, vec3(0.7, 0.0, 0.0)), 0.0);
+        assert!(IsoSource::Chroma.same(&IsoSource::Chroma));
+        assert!(!IsoSource::Chroma.same(&IsoSource::Color(Axis::Y)));
+        assert!(!IsoSource::Chroma.periodic(&oklab));
+    }
+}
@@ -0,0 +1,238 @@
+use std::{collections::HashMap, rc::Rc};
+
+use cgmath::{vec3, SquareMatrix};
+use three_d::{Context, Mat4, Vec3, VertexBuffer};
+
+use crate::{
+    embed::{ChunkRepresentation, Embedding, IdentityEmbedding},
+    input::InputState,
+    mesh::{
+        attribute::{Attribute, AttributeData},
+        CpuMesh, Mesh,
+    },
+    photo::Photo,
+};
+
+use super::{
+    colorspace::{input_matrix, WEDGE},
+    ColorElement, ColorModel, DrawMode, ModelGraph,
+};
+
+/// Most pixels read from a photo.
+const SAMPLES: usize = 100_000;
+/// Size of the Oklab cells whose pixels are drawn as one point.
+const CELL: f32 = 0.01;
+/// Size of a point holding a single pixel, in pixels. The densest cell is three times larger.
+const SIZE: f32 = 3.0;
+/// Slack for colors on the edge of the chunk.
+const EPSILON: f32 = 1e-4;
+
+fn cell(oklab: Vec3) -> (i32, i32, i32) {
+    (
+        (oklab.x / CELL).floor() as i32,
+        (oklab.y / CELL).floor() as i32,
+        (oklab.z / CELL).floor() as i32,
+    )
+}
+
+/// The pixel colors of a photo, placed where they are in the color solid.
+pub struct PointCloud {
+    photo: Option<Rc<Photo>>,
+    /// Mean Oklab color and point scale of every occupied cell of the photo.
+    cells: Vec<(Vec3, f32)>,
+    /// Point quads in space, with the Oklab `Color` and chunk `Input` position of every vertex.
+    mesh: Mesh,
+    view: Mat4,
+    model: Mat4,
+    input_matrix: Mat4,
+    chunk: Vec3,
+    color_embedding: Rc<dyn Embedding<Vec3>>,
+    space_embedding: Rc<dyn Embedding<Vec3>>,
+}
+
+impl PointCloud {
+    pub fn new(context: &Context) -> Self {
+        Self {
+            photo: None,
+            cells: Vec::new(),
+            mesh: Mesh::new(context, CpuMesh::new(Vec::new(), None)),
+            view: Mat4::identity(),
+            model: Mat4::identity(),
+            input_matrix: Mat4::identity(),
+            chunk: vec3(1.0, 1.0, 1.0),
+            color_embedding: Rc::new(IdentityEmbedding {}),
+            space_embedding: Rc::new(IdentityEmbedding {}),
+        }
+    }
+
+    fn buffer(&self, attribute: Attribute) -> &VertexBuffer {
+        self.mesh
+            .attribute_buffer(attribute)
+            .expect("point clouds carry input and color")
+    }
+
+    /// The points placed on the solid the same way `ColorSpace` places it, leaving out the
+    /// colors outside the chunk.
+    fn points(&self, state: &InputState) -> CpuMesh {
+        let representation = state.space_embedding.chunk_representation();
+        let hue = state.saved_pos.x;
+        let to_chunk = self.input_matrix.invert();
+        let mut colors = Vec::with_capacity(self.cells.len());
+        let mut inputs = Vec::with_capacity(self.cells.len());
+        let mut scales = Vec::with_capacity(self.cells.len());
+        for &(color, scale) in &self.cells {
+            let mut input = self.color_embedding.invert(color);
+            if representation == ChunkRepresentation::Wedge {
+                // The hue of the wedge starts at its cut, as in `input_matrix`
+                let start = hue + WEDGE;
+                input.x = start + (input.x - start).rem_euclid(1.0);
+            }
+            let input = match to_chunk {
+                Some(to_chunk) => (to_chunk * input.extend(1.0)).truncate(),
+                None => continue,
+            };
+            if input.x > 1.0 + EPSILON || input.y > 1.0 + EPSILON || input.z > 1.0 + EPSILON {
+                continue;
+            }
+            colors.push(color);
+            inputs.push(input);
+            scales.push(scale);
+        }
+        let to_input = |pos: Vec3| (self.input_matrix * pos.extend(1.0)).truncate();
+        let positions = inputs
+            .iter()
+            .map(|&pos| match representation {
+                ChunkRepresentation::Wedge => state.space_embedding.embed(to_input(pos)),
+                ChunkRepresentation::Clamp => state
+                    .space_embedding
+                    .embed(pos)
+                    .zip(state.chunk, |p, c| p.min(c)),
+                ChunkRepresentation::Scale => state.space_embedding.embed(pos),
+            })
+            .collect();
+        let mut mesh = CpuMesh::new(positions, None)
+            .with_attribute(Attribute::Color, AttributeData::Vec3(colors))
+            .with_attribute(Attribute::Input, AttributeData::Vec3(inputs))
+            .points();
+        if let Some(AttributeData::Vec2(corners)) = mesh.attributes.get_mut(&Attribute::Corner) {
+            for (i, corner) in corners.iter_mut().enumerate() {
+                *corner *= scales[i / 4];
+            }
+        }
+        mesh
+    }
+}
+
+/// One point per occupied Oklab cell at the mean color of its pixels, larger the more pixels
+/// it holds.
+fn cells(photo: &Photo) -> Vec<(Vec3, f32)> {
+    let mut cells: HashMap<(i32, i32, i32), (Vec3, u32)> = HashMap::new();
+    for color in photo.sample(SAMPLES) {
+        let (sum, count) = cells.entry(cell(color)).or_insert((vec3(0.0, 0.0, 0.0), 0));
+        *sum += color;
+        *count += 1;
+    }
+    let densest = cells.values().map(|&(_, count)| count).max().unwrap_or(1);
+    cells
+        .into_values()
+        .map(|(sum, count)| (sum / count as f32, scale(count, densest)))
+        .collect()
+}
+
+/// Grows from 1 for a single pixel to 3 for the densest cell.
+fn scale(count: u32, densest: u32) -> f32 {
+    1.0 + 2.0 * (1.0 + count as f32).ln() / (1.0 + densest as f32).ln()
+}
+
+impl ColorElement<InputState> for PointCloud {
+    fn update(&mut self, state: &InputState) {
+        self.view = state.camera.projection() * state.camera.view();
+        let photo_changed = match (&self.photo, &state.photo) {
+            (Some(photo), Some(other)) => !Rc::ptr_eq(photo, other),
+            (None, None) => false,
+            _ => true,
+        };
+        let representation = state.space_embedding.chunk_representation();
+        let input_matrix = input_matrix(representation, state.chunk, state.saved_pos.x);
+        let model = match representation {
+            ChunkRepresentation::Scale => {
+                Mat4::from_nonuniform_scale(state.chunk.x, state.chunk.y, state.chunk.z)
+            }
+            _ => Mat4::identity(),
+        };
+        let changed = photo_changed
+            || !Rc::<dyn Embedding>::ptr_eq(&self.color_embedding, &state.color_embedding)
+            || !Rc::<dyn Embedding>::ptr_eq(&self.space_embedding, &state.space_embedding)
+            || self.input_matrix != input_matrix
+            || self.chunk != state.chunk;
+        if !changed {
+            return;
+        }
+        if photo_changed {
+            self.photo = state.photo.clone();
+            self.cells = self.photo.as_deref().map(cells).unwrap_or_default();
+        }
+        self.color_embedding = state.color_embedding.clone();
+        self.space_embedding = state.space_embedding.clone();
+        self.input_matrix = input_matrix;
+        self.model = model;
+        self.chunk = state.chunk;
+        let points = self.points(state);
+        self.mesh.fill(&points);
+    }
+
+    fn model(&self) -> ModelGraph<'_> {
+        if self.mesh.positions().is_empty() {
+            return ModelGraph::Vec(Vec::new());
+        }
+        let draw_mode = DrawMode::Points { size: SIZE };
+        ModelGraph::Vec(vec![
+            ModelGraph::Color(ColorModel {
+                view: self.view,
+                model: self.model,
+                attributes: self.mesh.attribute_buffers(),
+                draw_mode,
+                ..ColorModel::new(
+                    self.mesh.vertex_buffer(),
+                    self.buffer(Attribute::Color),
+                    self.mesh.element_buffer(),
+                )
+            }),
+            ModelGraph::Space(ColorModel {
+                view: self.view,
+                model: self.model,
+                meta: self.input_matrix,
+                attributes: self.mesh.attribute_buffers(),
+                draw_mode,
+                ..ColorModel::new(
+                    self.mesh.vertex_buffer(),
+                    self.buffer(Attribute::Input),
+                    self.mesh.element_buffer(),
+                )
+            }),
+        ])
+    }
+
+    fn invert_space(&self, pos: Vec3) -> Option<Vec3> {
+        let axes = self.color_embedding.axes();
+        Some(vec3(
+            axes[0].wrap(pos.x),
+            axes[1].wrap(pos.y),
+            axes[2].wrap(pos.z),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_from_single_pixels_to_the_densest_cell() {
+        assert_eq!(scale(1, 1), 3.0);
+        assert_eq!(scale(1000, 1000), 3.0);
+        // A lone pixel next to a dense cell still grows a little
+        assert!(scale(1, 1000) > 1.0 && scale(1, 1000) < scale(2, 1000));
+        assert!(scale(2, 2) > scale(1, 2));
+    }
+}
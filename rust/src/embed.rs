@@ -1,8 +1,10 @@
-use std::f32::consts::PI;
+pub mod graph;
 
-use cgmath::{vec2, vec3, InnerSpace};
-use palette::{FromColor, LinSrgb, Okhsl, Okhsv, Oklab};
-use three_d::Vec3;
+use std::{f32::consts::PI, rc::Rc};
+
+use cgmath::{vec2, vec3, vec4, InnerSpace, Matrix, SquareMatrix};
+use palette::{FromColor, LinSrgb, Okhsl, Okhsv, Oklab, Srgb, Xyz};
+use three_d::{Mat3, Mat4, Vec3};
 
 use crate::element::coloraxis::Axis;
 
@@ -16,6 +18,87 @@ pub enum AxisRepresentation {
 pub enum ChunkRepresentation {
     Scale,
     Clamp,
+    /// Cuts a wedge of hues (input x) out after the selected hue, opening the solid at that hue.
+    Wedge,
+}
+
+/// Describes one input axis of an embedding. Inputs are always in `0..1`, `range` holds the
+/// native values at 0 and 1, e.g. `(0.0, 360.0)` for a hue in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisInfo {
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub range: (f32, f32),
+    pub periodic: bool,
+}
+
+impl AxisInfo {
+    pub const fn new(name: &'static str, unit: &'static str, range: (f32, f32)) -> Self {
+        Self {
+            name,
+            unit,
+            range,
+            periodic: false,
+        }
+    }
+
+    pub const fn unit(name: &'static str) -> Self {
+        Self::new(name, "", (0.0, 1.0))
+    }
+
+    pub const fn hue(unit: &'static str, turn: f32) -> Self {
+        Self {
+            name: "hue",
+            unit,
+            range: (0.0, turn),
+            periodic: true,
+        }
+    }
+
+    pub fn to_native(self, value: f32) -> f32 {
+        self.range.0 + value * (self.range.1 - self.range.0)
+    }
+
+    /// Brings an input value back into `0..1`, wrapping periodic axes and clamping others.
+    pub fn wrap(&self, value: f32) -> f32 {
+        if (0.0..=1.0).contains(&value) {
+            value
+        } else if self.periodic {
+            value.rem_euclid(1.0)
+        } else {
+            value.clamp(0.0, 1.0)
+        }
+    }
+}
+
+pub const XYZ_AXES: [AxisInfo; 3] = [
+    AxisInfo::unit("x"),
+    AxisInfo::unit("y"),
+    AxisInfo::unit("z"),
+];
+
+pub const RGB_AXES: [AxisInfo; 3] = [
+    AxisInfo::unit("red"),
+    AxisInfo::unit("green"),
+    AxisInfo::unit("blue"),
+];
+
+/// Positions that embeddings can be differentiated at.
+pub trait Point: Copy {
+    /// Central differences of `f` around `self`, column `i` is the derivative along axis `i`.
+    fn finite_jacobian<F: Fn(Self) -> Self>(self, f: F) -> Mat3;
+}
+
+impl Point for Vec3 {
+    fn finite_jacobian<F: Fn(Self) -> Self>(self, f: F) -> Mat3 {
+        let h = 1e-3;
+        let column = |axis: Vec3| (f(self + axis * h) - f(self - axis * h)) / (2.0 * h);
+        Mat3::from_cols(
+            column(Vec3::unit_x()),
+            column(Vec3::unit_y()),
+            column(Vec3::unit_z()),
+        )
+    }
 }
 
 pub trait StaticEmbedding<T = Vec3> {
@@ -27,6 +110,15 @@ pub trait StaticEmbedding<T = Vec3> {
     fn chunk_representation() -> ChunkRepresentation {
         ChunkRepresentation::Scale
     }
+    fn axes() -> [AxisInfo; 3] {
+        XYZ_AXES
+    }
+    fn static_jacobian(pos: T) -> Mat3
+    where
+        T: Point,
+    {
+        pos.finite_jacobian(Self::static_embed)
+    }
 }
 
 pub trait Embedding<T = Vec3> {
@@ -38,6 +130,19 @@ pub trait Embedding<T = Vec3> {
     fn chunk_representation(&self) -> ChunkRepresentation {
         ChunkRepresentation::Scale
     }
+    /// Metadata for the x, y and z input axes.
+    fn axes(&self) -> [AxisInfo; 3] {
+        XYZ_AXES
+    }
+    /// Derivative of `embed` at `pos`, column `i` is the derivative along input axis `i`.
+    ///
+    /// Uses finite differences unless the embedding knows better.
+    fn jacobian(&self, pos: T) -> Mat3
+    where
+        T: Point,
+    {
+        pos.finite_jacobian(|pos| self.embed(pos))
+    }
 }
 
 impl<T, U: StaticEmbedding<T>> Embedding<T> for U {
@@ -56,6 +161,17 @@ impl<T, U: StaticEmbedding<T>> Embedding<T> for U {
     fn chunk_representation(&self) -> ChunkRepresentation {
         Self::chunk_representation()
     }
+
+    fn axes(&self) -> [AxisInfo; 3] {
+        Self::axes()
+    }
+
+    fn jacobian(&self, pos: T) -> Mat3
+    where
+        T: Point,
+    {
+        Self::static_jacobian(pos)
+    }
 }
 
 pub struct IdentityEmbedding {}
@@ -69,6 +185,14 @@ impl StaticEmbedding<Vec3> for IdentityEmbedding {
         pos
     }
 
+    fn static_jacobian(_pos: Vec3) -> Mat3 {
+        Mat3::identity()
+    }
+
+    fn axes() -> [AxisInfo; 3] {
+        RGB_AXES
+    }
+
     fn axis_representation() -> AxisRepresentation {
         AxisRepresentation::Linear
     }
@@ -113,6 +237,106 @@ impl Embedding<Vec3> for ComposedEmbedding {
     fn chunk_representation(&self) -> ChunkRepresentation {
         self.chunk_representation
     }
+
+    fn axes(&self) -> [AxisInfo; 3] {
+        self.b.axes()
+    }
+
+    fn jacobian(&self, pos: Vec3) -> Mat3 {
+        self.a.jacobian(self.b.embed(pos)) * self.b.jacobian(pos)
+    }
+}
+
+/// Applies `steps` in order, so the output of each step is the input of the next.
+pub struct ChainEmbedding {
+    pub steps: Vec<Rc<dyn Embedding<Vec3>>>,
+}
+
+impl ChainEmbedding {
+    pub fn new(steps: Vec<Rc<dyn Embedding<Vec3>>>) -> Self {
+        Self { steps }
+    }
+
+    /// Every intermediate position, starting with `pos` and ending with the embedded result.
+    pub fn trace(&self, pos: Vec3) -> Vec<Vec3> {
+        let mut trace = vec![pos];
+        for step in &self.steps {
+            trace.push(step.embed(*trace.last().unwrap()));
+        }
+        trace
+    }
+}
+
+impl Embedding<Vec3> for ChainEmbedding {
+    fn embed(&self, pos: Vec3) -> Vec3 {
+        self.steps.iter().fold(pos, |pos, step| step.embed(pos))
+    }
+
+    fn invert(&self, pos: Vec3) -> Vec3 {
+        self.steps
+            .iter()
+            .rev()
+            .fold(pos, |pos, step| step.invert(pos))
+    }
+
+    fn axis_representation(&self) -> AxisRepresentation {
+        match self.steps.first() {
+            Some(step) => step.axis_representation(),
+            None => AxisRepresentation::Linear,
+        }
+    }
+
+    fn chunk_representation(&self) -> ChunkRepresentation {
+        match self.steps.first() {
+            Some(step) => step.chunk_representation(),
+            None => ChunkRepresentation::Scale,
+        }
+    }
+
+    fn axes(&self) -> [AxisInfo; 3] {
+        match self.steps.first() {
+            Some(step) => step.axes(),
+            None => XYZ_AXES,
+        }
+    }
+
+    fn jacobian(&self, pos: Vec3) -> Mat3 {
+        let (_, jacobian) = self
+            .steps
+            .iter()
+            .fold((pos, Mat3::identity()), |(pos, jacobian), step| {
+                (step.embed(pos), step.jacobian(pos) * jacobian)
+            });
+        jacobian
+    }
+}
+
+/// Swaps `embed` and `invert` of another embedding.
+pub struct InverseEmbedding {
+    pub inner: Rc<dyn Embedding<Vec3>>,
+}
+
+impl InverseEmbedding {
+    pub fn new(inner: Rc<dyn Embedding<Vec3>>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Embedding<Vec3> for InverseEmbedding {
+    fn embed(&self, pos: Vec3) -> Vec3 {
+        self.inner.invert(pos)
+    }
+
+    fn invert(&self, pos: Vec3) -> Vec3 {
+        self.inner.embed(pos)
+    }
+
+    fn jacobian(&self, pos: Vec3) -> Mat3 {
+        let jacobian = self.inner.jacobian(self.inner.invert(pos));
+        jacobian
+            .invert()
+            .unwrap_or_else(|| pos.finite_jacobian(|pos| self.embed(pos)))
+    }
 }
 
 pub struct SwapAxesEmbedding {
@@ -142,6 +366,82 @@ impl Embedding<Vec3> for SwapAxesEmbedding {
     }
 }
 
+/// Embeds with an arbitrary 4x4 matrix, dividing by `w` when the matrix is projective.
+pub struct AffineEmbedding {
+    pub matrix: Mat4,
+    pub inverse: Mat4,
+    pub axis_representation: AxisRepresentation,
+    pub chunk_representation: ChunkRepresentation,
+}
+
+impl AffineEmbedding {
+    /// Returns `None` when `matrix` can't be inverted.
+    pub fn new(matrix: Mat4) -> Option<Self> {
+        Some(Self::from_parts(matrix, matrix.invert()?))
+    }
+
+    /// Uses a known inverse, avoiding the rounding of a numerical inversion.
+    pub fn from_parts(matrix: Mat4, inverse: Mat4) -> Self {
+        Self {
+            matrix,
+            inverse,
+            axis_representation: AxisRepresentation::Linear,
+            chunk_representation: ChunkRepresentation::Scale,
+        }
+    }
+
+    /// Maps each axis from `min..max` to `0..1`, e.g. Oklab a and b from `-0.4..0.4`.
+    pub fn normalize(min: Vec3, max: Vec3) -> Self {
+        let size = max - min;
+        let matrix = Mat4::from_nonuniform_scale(1.0 / size.x, 1.0 / size.y, 1.0 / size.z)
+            * Mat4::from_translation(-min);
+        let inverse =
+            Mat4::from_translation(min) * Mat4::from_nonuniform_scale(size.x, size.y, size.z);
+        Self::from_parts(matrix, inverse)
+    }
+
+    /// Moves input axis `axes[i]` to output axis `i`.
+    pub fn permutation(axes: [Axis; 3]) -> Self {
+        let mut matrix = Mat4::from_scale(0.0);
+        for (i, axis) in axes.iter().enumerate() {
+            matrix[*axis as usize][i] = 1.0;
+        }
+        Self::from_parts(matrix, matrix.transpose())
+    }
+
+    fn transform(matrix: &Mat4, pos: Vec3) -> Vec3 {
+        let pos = matrix * vec4(pos.x, pos.y, pos.z, 1.0);
+        pos.truncate() / pos.w
+    }
+}
+
+impl Embedding<Vec3> for AffineEmbedding {
+    fn embed(&self, pos: Vec3) -> Vec3 {
+        Self::transform(&self.matrix, pos)
+    }
+
+    fn invert(&self, pos: Vec3) -> Vec3 {
+        Self::transform(&self.inverse, pos)
+    }
+
+    fn axis_representation(&self) -> AxisRepresentation {
+        self.axis_representation
+    }
+
+    fn chunk_representation(&self) -> ChunkRepresentation {
+        self.chunk_representation
+    }
+
+    fn jacobian(&self, pos: Vec3) -> Mat3 {
+        let m = &self.matrix;
+        if m.x.w == 0.0 && m.y.w == 0.0 && m.z.w == 0.0 {
+            Mat3::from_cols(m.x.truncate(), m.y.truncate(), m.z.truncate()) / m.w.w
+        } else {
+            pos.finite_jacobian(|pos| self.embed(pos))
+        }
+    }
+}
+
 pub struct CylindricalEmbedding {}
 
 impl StaticEmbedding<Vec3> for CylindricalEmbedding {
@@ -155,12 +455,29 @@ impl StaticEmbedding<Vec3> for CylindricalEmbedding {
 
     fn static_invert(pos: Vec3) -> Vec3 {
         let r = vec2(pos.x, pos.z).magnitude();
-        let h = (-pos.z.atan2(pos.x) / PI / 2.0).rem_euclid(1.0);
+        let h = (pos.z.atan2(pos.x) / PI / 2.0).rem_euclid(1.0);
         let x = h;
         let y = pos.y;
         let z = r;
         vec3(x, y, z)
     }
+
+    fn static_jacobian(pos: Vec3) -> Mat3 {
+        let h = pos.x * PI * 2.0;
+        Mat3::from_cols(
+            vec3(-h.sin() * pos.z, 0.0, h.cos() * pos.z) * PI * 2.0,
+            Vec3::unit_y(),
+            vec3(h.cos(), 0.0, h.sin()),
+        )
+    }
+
+    fn axes() -> [AxisInfo; 3] {
+        [
+            AxisInfo::hue("turn", 1.0),
+            AxisInfo::unit("height"),
+            AxisInfo::unit("radius"),
+        ]
+    }
 }
 
 pub struct OkhsvEmbedding {}
@@ -180,6 +497,14 @@ impl StaticEmbedding<Vec3> for OkhsvEmbedding {
         let v = hsv.value;
         vec3(h, v, s)
     }
+
+    fn axes() -> [AxisInfo; 3] {
+        [
+            AxisInfo::hue("°", 360.0),
+            AxisInfo::new("value", "%", (0.0, 100.0)),
+            AxisInfo::new("saturation", "%", (0.0, 100.0)),
+        ]
+    }
 }
 
 pub struct OkhslEmbedding {}
@@ -199,6 +524,14 @@ impl StaticEmbedding<Vec3> for OkhslEmbedding {
         let l = hsl.lightness;
         vec3(h, l, s)
     }
+
+    fn axes() -> [AxisInfo; 3] {
+        [
+            AxisInfo::hue("°", 360.0),
+            AxisInfo::new("lightness", "%", (0.0, 100.0)),
+            AxisInfo::new("saturation", "%", (0.0, 100.0)),
+        ]
+    }
 }
 
 pub struct LinSrgbOklabEmbedding {}
@@ -219,12 +552,105 @@ impl StaticEmbedding<Vec3> for LinSrgbOklabEmbedding {
         vec3(r, g, b)
     }
 
+    fn axes() -> [AxisInfo; 3] {
+        RGB_AXES
+    }
+
+    fn chunk_representation() -> ChunkRepresentation {
+        ChunkRepresentation::Clamp
+    }
+}
+
+/// sRGB in `0..1`, clamped into gamut, of an Oklab color.
+pub fn oklab_to_srgb(oklab: Vec3) -> Vec3 {
+    let srgb = Srgb::from_color(Oklab::new(oklab.x, oklab.y, oklab.z));
+    vec3(srgb.red, srgb.green, srgb.blue).map(|c| c.clamp(0.0, 1.0))
+}
+
+pub fn srgb_to_oklab(srgb: Vec3) -> Vec3 {
+    let oklab = Oklab::from_color(Srgb::new(srgb.x, srgb.y, srgb.z).into_linear::<f32>());
+    vec3(oklab.l, oklab.a, oklab.b)
+}
+
+pub struct SrgbLinSrgbEmbedding {}
+
+impl StaticEmbedding<Vec3> for SrgbLinSrgbEmbedding {
+    fn static_embed(pos: Vec3) -> Vec3 {
+        let lin_srgb: LinSrgb = Srgb::new(pos.x, pos.y, pos.z).into_linear();
+        vec3(lin_srgb.red, lin_srgb.green, lin_srgb.blue)
+    }
+
+    fn static_invert(pos: Vec3) -> Vec3 {
+        let srgb = Srgb::from_linear(LinSrgb::new(pos.x, pos.y, pos.z));
+        vec3(srgb.red, srgb.green, srgb.blue)
+    }
+
+    fn axes() -> [AxisInfo; 3] {
+        RGB_AXES
+    }
+
+    fn axis_representation() -> AxisRepresentation {
+        AxisRepresentation::Linear
+    }
+
     fn chunk_representation() -> ChunkRepresentation {
         ChunkRepresentation::Clamp
     }
 }
 
-pub struct FnEmbedding<F1, F2>
+pub struct LinSrgbXyzEmbedding {}
+
+impl StaticEmbedding<Vec3> for LinSrgbXyzEmbedding {
+    fn static_embed(pos: Vec3) -> Vec3 {
+        let xyz = Xyz::from_color(LinSrgb::new(pos.x, pos.y, pos.z));
+        vec3(xyz.x, xyz.y, xyz.z)
+    }
+
+    fn static_invert(pos: Vec3) -> Vec3 {
+        let lin_srgb = LinSrgb::from_color(Xyz::new(pos.x, pos.y, pos.z));
+        vec3(lin_srgb.red, lin_srgb.green, lin_srgb.blue)
+    }
+
+    fn axes() -> [AxisInfo; 3] {
+        RGB_AXES
+    }
+
+    fn axis_representation() -> AxisRepresentation {
+        AxisRepresentation::Linear
+    }
+
+    fn chunk_representation() -> ChunkRepresentation {
+        ChunkRepresentation::Clamp
+    }
+}
+
+pub struct XyzOklabEmbedding {}
+
+impl StaticEmbedding<Vec3> for XyzOklabEmbedding {
+    fn static_embed(pos: Vec3) -> Vec3 {
+        let oklab = Oklab::from_color(Xyz::new(pos.x, pos.y, pos.z));
+        vec3(oklab.l, oklab.a, oklab.b)
+    }
+
+    fn static_invert(pos: Vec3) -> Vec3 {
+        let xyz = Xyz::from_color(Oklab::new(pos.x, pos.y, pos.z));
+        vec3(xyz.x, xyz.y, xyz.z)
+    }
+
+    fn axes() -> [AxisInfo; 3] {
+        [
+            AxisInfo::unit("X"),
+            AxisInfo::unit("Y"),
+            AxisInfo::unit("Z"),
+        ]
+    }
+
+    fn axis_representation() -> AxisRepresentation {
+        AxisRepresentation::Linear
+    }
+}
+
+pub struct _FnEmbedding<F1, F2>
 where
     F1: Fn(Vec3) -> Vec3,
     F2: Fn(Vec3) -> Vec3,
@@ -233,7 +659,7 @@ where
     invert: F2,
 }
 
-impl<F1, F2> FnEmbedding<F1, F2>
+impl<F1, F2> _FnEmbedding<F1, F2>
 where
     F1: Fn(Vec3) -> Vec3,
     F2: Fn(Vec3) -> Vec3,
@@ -243,7 +669,7 @@ where
     }
 }
 
-impl<F1, F2> Embedding<Vec3> for FnEmbedding<F1, F2>
+impl<F1, F2> Embedding<Vec3> for _FnEmbedding<F1, F2>
 where
     F1: Fn(Vec3) -> Vec3,
     F2: Fn(Vec3) -> Vec3,
@@ -256,3 +682,98 @@ where
         (self.invert)(pos)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < 1e-5, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn cylindrical_invert_undoes_embed() {
+        for hue in [0.05, 0.3, 0.55, 0.8] {
+            let pos = vec3(hue, 0.4, 0.7);
+            let embedded = CylindricalEmbedding::static_embed(pos);
+            assert_close(CylindricalEmbedding::static_invert(embedded), pos);
+        }
+    }
+
+    #[test]
+    fn normalize_maps_to_unit_cube() {
+        let min = vec3(0.0, -0.4, -0.4);
+        let max = vec3(1.0, 0.4, 0.4);
+        let normalize = AffineEmbedding::normalize(min, max);
+        assert_close(normalize.embed(min), vec3(0.0, 0.0, 0.0));
+        assert_close(normalize.embed(max), vec3(1.0, 1.0, 1.0));
+        let pos = vec3(0.7, 0.1, -0.3);
+        assert_close(normalize.embed(pos), vec3(0.7, 0.625, 0.125));
+        assert_close(normalize.invert(normalize.embed(pos)), pos);
+    }
+
+    #[test]
+    fn permutation_moves_axes() {
+        let pos = vec3(1.0, 2.0, 3.0);
+        let cycle = AffineEmbedding::permutation([Axis::Y, Axis::Z, Axis::X]);
+        assert_close(cycle.embed(pos), vec3(2.0, 3.0, 1.0));
+        assert_close(cycle.invert(cycle.embed(pos)), pos);
+        assert_close(cycle.embed(cycle.embed(cycle.embed(pos))), pos);
+
+        let swap = AffineEmbedding::permutation([Axis::Z, Axis::Y, Axis::X]);
+        assert_close(swap.embed(pos), vec3(3.0, 2.0, 1.0));
+        assert_close(swap.embed(swap.embed(pos)), pos);
+        assert_eq!(swap.matrix * swap.matrix, Mat4::identity());
+    }
+
+    /// Compares the jacobian of `embedding` with finite differences at a few positions.
+    fn assert_jacobian(embedding: &dyn Embedding<Vec3>) {
+        for pos in [
+            vec3(0.2, 0.5, 0.3),
+            vec3(0.7, 0.3, 0.6),
+            vec3(0.45, 0.8, 0.1),
+        ] {
+            let analytic = embedding.jacobian(pos);
+            let finite = pos.finite_jacobian(|pos| embedding.embed(pos));
+            for axis in 0..3 {
+                let error = (analytic[axis] - finite[axis]).magnitude();
+                assert!(
+                    error < 1e-2 * finite[axis].magnitude().max(1.0),
+                    "column {axis} at {pos:?}: {analytic:?} != {finite:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn jacobians_match_finite_differences() {
+        assert_jacobian(&IdentityEmbedding {});
+        assert_jacobian(&CylindricalEmbedding {});
+        assert_jacobian(&graph::LinDisplayP3XyzEmbedding {});
+        assert_jacobian(&AffineEmbedding::normalize(
+            vec3(0.0, -0.4, -0.4),
+            vec3(1.0, 0.4, 0.4),
+        ));
+        let projective = Mat4::from_cols(
+            vec4(1.0, 0.0, 0.0, 0.2),
+            vec4(0.0, 2.0, 0.0, 0.0),
+            vec4(0.0, 0.0, 1.0, 0.1),
+            vec4(0.0, 0.0, 0.0, 1.0),
+        );
+        assert_jacobian(&AffineEmbedding::new(projective).unwrap());
+        assert_jacobian(&ComposedEmbedding::new(
+            Box::new(CylindricalEmbedding {}),
+            Box::new(AffineEmbedding::permutation([Axis::Z, Axis::X, Axis::Y])),
+            AxisRepresentation::Linear,
+            ChunkRepresentation::Scale,
+        ));
+        let steps: Vec<Rc<dyn Embedding<Vec3>>> =
+            vec![Rc::new(OkhsvEmbedding {}), Rc::new(CylindricalEmbedding {})];
+        assert_jacobian(&ChainEmbedding::new(steps));
+        assert_jacobian(&InverseEmbedding::new(Rc::new(AffineEmbedding::normalize(
+            vec3(0.0, -0.4, -0.4),
+            vec3(1.0, 0.4, 0.4),
+        ))));
+        assert_jacobian(&InverseEmbedding::new(Rc::new(CylindricalEmbedding {})));
+    }
+}
@@ -0,0 +1,539 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
+
+use cgmath::{Matrix, Matrix3};
+use three_d::{Mat3, Vec3};
+
+use super::{
+    AxisInfo, ChainEmbedding, Embedding, InverseEmbedding, LinSrgbOklabEmbedding,
+    LinSrgbXyzEmbedding, OkhslEmbedding, OkhsvEmbedding, SrgbLinSrgbEmbedding, StaticEmbedding,
+    XyzOklabEmbedding, RGB_AXES,
+};
+
+/// Embeds linear Display P3 into XYZ (D65).
+pub struct LinDisplayP3XyzEmbedding {}
+
+#[rustfmt::skip]
+fn display_p3_to_xyz() -> Mat3 {
+    Matrix3::new(
+        0.48657095, 0.26566769, 0.19821729,
+        0.22897456, 0.69173852, 0.07928691,
+        0.0, 0.04511338, 1.0439444,
+    )
+    .transpose()
+}
+
+#[rustfmt::skip]
+fn xyz_to_display_p3() -> Mat3 {
+    Matrix3::new(
+        2.493497, -0.9313836, -0.40271078,
+        -0.829489, 1.7626641, 0.02362469,
+        0.03584583, -0.07617239, 0.9568845,
+    )
+    .transpose()
+}
+
+impl StaticEmbedding<Vec3> for LinDisplayP3XyzEmbedding {
+    fn static_embed(pos: Vec3) -> Vec3 {
+        display_p3_to_xyz() * pos
+    }
+
+    fn static_invert(pos: Vec3) -> Vec3 {
+        xyz_to_display_p3() * pos
+    }
+
+    fn static_jacobian(_pos: Vec3) -> Mat3 {
+        display_p3_to_xyz()
+    }
+
+    fn axes() -> [AxisInfo; 3] {
+        RGB_AXES
+    }
+}
+
+/// A linear map between RGB-like spaces, such as linear RGB primaries to XYZ.
+pub struct MatrixEmbedding {
+    matrix: Mat3,
+    inverse: Mat3,
+}
+
+impl MatrixEmbedding {
+    /// `inverse` is written out with `matrix` since published pairs round better than
+    /// inverting in `f32`.
+    fn new(matrix: Mat3, inverse: Mat3) -> Self {
+        Self { matrix, inverse }
+    }
+}
+
+impl Embedding<Vec3> for MatrixEmbedding {
+    fn embed(&self, pos: Vec3) -> Vec3 {
+        self.matrix * pos
+    }
+
+    fn invert(&self, pos: Vec3) -> Vec3 {
+        self.inverse * pos
+    }
+
+    fn axes(&self) -> [AxisInfo; 3] {
+        RGB_AXES
+    }
+
+    fn jacobian(&self, _pos: Vec3) -> Mat3 {
+        self.matrix
+    }
+}
+
+/// Decodes each channel of a gamma encoded RGB space, mirrored for negative values.
+pub struct TransferEmbedding {
+    decode: fn(f32) -> f32,
+    encode: fn(f32) -> f32,
+}
+
+fn mirrored(f: fn(f32) -> f32, c: f32) -> f32 {
+    f(c.abs()).copysign(c)
+}
+
+impl Embedding<Vec3> for TransferEmbedding {
+    fn embed(&self, pos: Vec3) -> Vec3 {
+        pos.map(|c| mirrored(self.decode, c))
+    }
+
+    fn invert(&self, pos: Vec3) -> Vec3 {
+        pos.map(|c| mirrored(self.encode, c))
+    }
+
+    fn axes(&self) -> [AxisInfo; 3] {
+        RGB_AXES
+    }
+}
+
+const REC2020_ALPHA: f32 = 1.099_296_8;
+const REC2020_BETA: f32 = 0.018_053_97;
+
+fn rec2020_transfer() -> TransferEmbedding {
+    TransferEmbedding {
+        decode: |c| {
+            if c < REC2020_ALPHA * REC2020_BETA.powf(0.45) - (REC2020_ALPHA - 1.0) {
+                c / 4.5
+            } else {
+                ((c + REC2020_ALPHA - 1.0) / REC2020_ALPHA).powf(1.0 / 0.45)
+            }
+        },
+        encode: |c| {
+            if c < REC2020_BETA {
+                c * 4.5
+            } else {
+                REC2020_ALPHA * c.powf(0.45) - (REC2020_ALPHA - 1.0)
+            }
+        },
+    }
+}
+
+fn a98_rgb_transfer() -> TransferEmbedding {
+    TransferEmbedding {
+        decode: |c| c.powf(563.0 / 256.0),
+        encode: |c| c.powf(256.0 / 563.0),
+    }
+}
+
+fn prophoto_rgb_transfer() -> TransferEmbedding {
+    TransferEmbedding {
+        decode: |c| {
+            if c <= 16.0 / 512.0 {
+                c / 16.0
+            } else {
+                c.powf(1.8)
+            }
+        },
+        encode: |c| {
+            if c < 1.0 / 512.0 {
+                c * 16.0
+            } else {
+                c.powf(1.0 / 1.8)
+            }
+        },
+    }
+}
+
+#[rustfmt::skip]
+fn rec2020() -> MatrixEmbedding {
+    MatrixEmbedding::new(
+        Matrix3::new(
+            0.63695805, 0.1446169, 0.16888098,
+            0.2627002, 0.6779981, 0.05930172,
+            0.0, 0.02807269, 1.0609851,
+        )
+        .transpose(),
+        Matrix3::new(
+            1.7166512, -0.35567078, -0.2533663,
+            -0.6666843, 1.6164812, 0.01576855,
+            0.01763986, -0.04277061, 0.94210312,
+        )
+        .transpose(),
+    )
+}
+
+#[rustfmt::skip]
+fn a98_rgb() -> MatrixEmbedding {
+    MatrixEmbedding::new(
+        Matrix3::new(
+            0.57666904, 0.18555824, 0.18822865,
+            0.29734498, 0.62736357, 0.07529146,
+            0.02703136, 0.07068885, 0.99133754,
+        )
+        .transpose(),
+        Matrix3::new(
+            2.0415879, -0.565007, -0.34473135,
+            -0.96924364, 1.8759675, 0.04155506,
+            0.01344428, -0.11836239, 1.015175,
+        )
+        .transpose(),
+    )
+}
+
+/// Into XYZ under D50, the white point of ProPhoto RGB.
+#[rustfmt::skip]
+fn prophoto_rgb() -> MatrixEmbedding {
+    MatrixEmbedding::new(
+        Matrix3::new(
+            0.7977666, 0.1351813, 0.03134773,
+            0.28807483, 0.7118352, 0.00008994,
+            0.0, 0.0, 0.8251046,
+        )
+        .transpose(),
+        Matrix3::new(
+            1.3457869, -0.25557209, -0.05110186,
+            -0.5446307, 1.5082477, 0.02052745,
+            0.0, 0.0, 1.2119675,
+        )
+        .transpose(),
+    )
+}
+
+/// Bradford chromatic adaptation from D50 to D65.
+#[rustfmt::skip]
+fn d50_to_d65() -> MatrixEmbedding {
+    MatrixEmbedding::new(
+        Matrix3::new(
+            0.9554734, -0.02309845, 0.06325924,
+            -0.02836971, 1.0099954, 0.02104144,
+            0.01231401, -0.02050765, 1.3303659,
+        )
+        .transpose(),
+        Matrix3::new(
+            1.0479298, 0.02294687, -0.05019227,
+            0.02962781, 0.9904344, -0.0170738,
+            -0.00924304, 0.01505519, 0.75187428,
+        )
+        .transpose(),
+    )
+}
+
+/// Spaces visited by a search, and the steps converting between consecutive ones.
+type Route = (Vec<usize>, Vec<Rc<dyn Embedding<Vec3>>>);
+
+struct Conversion {
+    from: usize,
+    to: usize,
+    embedding: Rc<dyn Embedding<Vec3>>,
+}
+
+/// Color spaces connected by conversions.
+///
+/// Every conversion can be walked in both directions, so `convert` finds the shortest chain of
+/// conversions between any two connected spaces. Resolved chains are cached by space names.
+pub struct ConversionGraph {
+    spaces: Vec<String>,
+    conversions: Vec<Conversion>,
+    cache: RefCell<HashMap<(String, String), Rc<ChainEmbedding>>>,
+}
+
+impl ConversionGraph {
+    pub fn new() -> Self {
+        Self {
+            spaces: Vec::new(),
+            conversions: Vec::new(),
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn space_index(&mut self, name: &str) -> usize {
+        match self.index(name) {
+            Some(index) => index,
+            None => {
+                self.spaces.push(name.to_string());
+                self.spaces.len() - 1
+            }
+        }
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.spaces.iter().position(|space| space == name)
+    }
+
+    pub fn spaces(&self) -> &[String] {
+        &self.spaces
+    }
+
+    /// Adds a conversion whose `embed` maps positions in `from` to positions in `to`.
+    pub fn add_conversion(&mut self, from: &str, to: &str, embedding: Rc<dyn Embedding<Vec3>>) {
+        let from = self.space_index(from);
+        let to = self.space_index(to);
+        self.conversions.push(Conversion {
+            from,
+            to,
+            embedding,
+        });
+        self.cache.borrow_mut().clear();
+    }
+
+    /// Names of the spaces visited on the shortest way from `from` to `to`, both included.
+    pub fn path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let (spaces, _) = self.search(from, to)?;
+        Some(spaces.iter().map(|&i| self.spaces[i].clone()).collect())
+    }
+
+    fn search(&self, from: &str, to: &str) -> Option<Route> {
+        let from = self.index(from)?;
+        let to = self.index(to)?;
+        let mut previous: Vec<Option<(usize, usize, bool)>> = vec![None; self.spaces.len()];
+        let mut visited = vec![false; self.spaces.len()];
+        let mut queue = VecDeque::from([from]);
+        visited[from] = true;
+        while let Some(space) = queue.pop_front() {
+            if space == to {
+                break;
+            }
+            for (i, conversion) in self.conversions.iter().enumerate() {
+                let (next, forward) = if conversion.from == space {
+                    (conversion.to, true)
+                } else if conversion.to == space {
+                    (conversion.from, false)
+                } else {
+                    continue;
+                };
+                if !visited[next] {
+                    visited[next] = true;
+                    previous[next] = Some((space, i, forward));
+                    queue.push_back(next);
+                }
+            }
+        }
+        if !visited[to] {
+            return None;
+        }
+
+        let mut spaces = vec![to];
+        let mut steps: Vec<Rc<dyn Embedding<Vec3>>> = Vec::new();
+        let mut space = to;
+        while let Some((prev, i, forward)) = previous[space] {
+            let embedding = self.conversions[i].embedding.clone();
+            steps.push(if forward {
+                embedding
+            } else {
+                Rc::new(InverseEmbedding::new(embedding))
+            });
+            spaces.push(prev);
+            space = prev;
+        }
+        spaces.reverse();
+        steps.reverse();
+        Some((spaces, steps))
+    }
+
+    /// Embedding mapping positions in `from` to positions in `to`, e.g.
+    /// `convert("okhsl", "display-p3")`.
+    pub fn convert(&self, from: &str, to: &str) -> Option<Rc<ChainEmbedding>> {
+        let key = (from.to_string(), to.to_string());
+        if let Some(chain) = self.cache.borrow().get(&key) {
+            return Some(chain.clone());
+        }
+        let (_, steps) = self.search(from, to)?;
+        let chain = Rc::new(ChainEmbedding::new(steps));
+        self.cache.borrow_mut().insert(key, chain.clone());
+        Some(chain)
+    }
+}
+
+impl Default for ConversionGraph {
+    fn default() -> Self {
+        let mut graph = Self::new();
+        graph.add_conversion("srgb", "linear-srgb", Rc::new(SrgbLinSrgbEmbedding {}));
+        graph.add_conversion("linear-srgb", "xyz", Rc::new(LinSrgbXyzEmbedding {}));
+        graph.add_conversion("linear-srgb", "oklab", Rc::new(LinSrgbOklabEmbedding {}));
+        graph.add_conversion("xyz", "oklab", Rc::new(XyzOklabEmbedding {}));
+        graph.add_conversion("okhsv", "oklab", Rc::new(OkhsvEmbedding {}));
+        graph.add_conversion("okhsl", "oklab", Rc::new(OkhslEmbedding {}));
+        graph.add_conversion(
+            "linear-display-p3",
+            "xyz",
+            Rc::new(LinDisplayP3XyzEmbedding {}),
+        );
+        // Display P3 shares the sRGB transfer function.
+        graph.add_conversion(
+            "display-p3",
+            "linear-display-p3",
+            Rc::new(SrgbLinSrgbEmbedding {}),
+        );
+        graph.add_conversion("xyz-d50", "xyz", Rc::new(d50_to_d65()));
+        graph.add_conversion("linear-rec2020", "xyz", Rc::new(rec2020()));
+        graph.add_conversion("rec2020", "linear-rec2020", Rc::new(rec2020_transfer()));
+        graph.add_conversion("linear-a98-rgb", "xyz", Rc::new(a98_rgb()));
+        graph.add_conversion("a98-rgb", "linear-a98-rgb", Rc::new(a98_rgb_transfer()));
+        graph.add_conversion("linear-prophoto-rgb", "xyz-d50", Rc::new(prophoto_rgb()));
+        graph.add_conversion(
+            "prophoto-rgb",
+            "linear-prophoto-rgb",
+            Rc::new(prophoto_rgb_transfer()),
+        );
+        graph
+    }
+}
+
+thread_local! {
+    static GRAPH: ConversionGraph = ConversionGraph::default();
+}
+
+/// Resolves a conversion in the default graph of supported color spaces.
+pub fn convert(from: &str, to: &str) -> Option<Rc<ChainEmbedding>> {
+    GRAPH.with(|graph| graph.convert(from, to))
+}
+
+/// Spaces visited when converting in the default graph, see [`ConversionGraph::path`].
+pub fn path(from: &str, to: &str) -> Option<Vec<String>> {
+    GRAPH.with(|graph| graph.path(from, to))
+}
+
+/// Names of every space in the default graph.
+pub fn spaces() -> Vec<String> {
+    GRAPH.with(|graph| graph.spaces().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use super::*;
+    use crate::embed::IdentityEmbedding;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < 1e-4, "{a:?} != {b:?}");
+    }
+
+    fn names(path: &[&str]) -> Vec<String> {
+        path.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn finds_shortest_paths() {
+        let graph = ConversionGraph::default();
+        assert_eq!(
+            graph.path("srgb", "oklab"),
+            Some(names(&["srgb", "linear-srgb", "oklab"]))
+        );
+        assert_eq!(
+            graph.path("display-p3", "okhsl"),
+            Some(names(&[
+                "display-p3",
+                "linear-display-p3",
+                "xyz",
+                "oklab",
+                "okhsl"
+            ]))
+        );
+        assert_eq!(graph.path("xyz", "xyz"), Some(names(&["xyz"])));
+    }
+
+    #[test]
+    fn rejects_unknown_spaces() {
+        let graph = ConversionGraph::default();
+        assert!(graph.path("srgb", "cmyk").is_none());
+        assert!(graph.convert("cmyk", "srgb").is_none());
+
+        let mut graph = ConversionGraph::new();
+        graph.add_conversion("a", "b", Rc::new(IdentityEmbedding {}));
+        graph.add_conversion("c", "d", Rc::new(IdentityEmbedding {}));
+        assert!(graph.path("a", "d").is_none());
+    }
+
+    #[test]
+    fn caches_conversions() {
+        let mut graph = ConversionGraph::default();
+        let first = graph.convert("srgb", "okhsv").unwrap();
+        let second = graph.convert("srgb", "okhsv").unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+
+        // Adding conversions may shorten paths, so it drops the cache
+        graph.add_conversion("srgb", "okhsv", Rc::new(IdentityEmbedding {}));
+        let third = graph.convert("srgb", "okhsv").unwrap();
+        assert!(!Rc::ptr_eq(&first, &third));
+        assert_eq!(graph.path("srgb", "okhsv").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn converts_both_ways() {
+        let graph = ConversionGraph::default();
+        let colors = [
+            Vec3::new(0.2, 0.5, 0.8),
+            Vec3::new(0.9, 0.1, 0.3),
+            Vec3::new(0.5, 0.45, 0.4),
+        ];
+        for from in graph.spaces() {
+            for to in ["srgb", "oklab", "display-p3"] {
+                let conversion = graph.convert(from, to).unwrap();
+                let back = graph.convert(to, from).unwrap();
+                for color in colors {
+                    let color = graph.convert("srgb", from).unwrap().embed(color);
+                    assert_close(conversion.invert(conversion.embed(color)), color);
+                    assert_close(back.embed(conversion.embed(color)), color);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn converts_display_p3() {
+        let graph = ConversionGraph::default();
+        // Both spaces share the D65 white point
+        let white = graph.convert("display-p3", "xyz").unwrap();
+        assert_close(
+            white.embed(Vec3::new(1.0, 1.0, 1.0)),
+            Vec3::new(0.95046, 1.0, 1.08906),
+        );
+        // sRGB red lies inside the wider Display P3 gamut
+        let red = graph.convert("srgb", "display-p3").unwrap();
+        assert_close(
+            red.embed(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.91749, 0.20029, 0.13856),
+        );
+        assert_close(
+            (display_p3_to_xyz() * xyz_to_display_p3()) * Vec3::new(0.3, 0.6, 0.9),
+            Vec3::new(0.3, 0.6, 0.9),
+        );
+    }
+
+    #[test]
+    fn published_inverses_match() {
+        for matrix in [rec2020(), a98_rgb(), prophoto_rgb(), d50_to_d65()] {
+            let product = matrix.matrix * matrix.inverse;
+            for (i, column) in [product.x, product.y, product.z].into_iter().enumerate() {
+                let mut unit = Vec3::new(0.0, 0.0, 0.0);
+                unit[i] = 1.0;
+                assert_close(column, unit);
+            }
+        }
+        // Every RGB white is the white point of its XYZ
+        let d65 = Vec3::new(0.950456, 1.0, 1.089058);
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        assert_close(rec2020().embed(white), d65);
+        assert_close(a98_rgb().embed(white), d65);
+        assert_close(
+            prophoto_rgb().embed(white),
+            Vec3::new(0.964296, 1.0, 0.825105),
+        );
+        assert_close(d50_to_d65().embed(Vec3::new(0.964296, 1.0, 0.825105)), d65);
+    }
+}
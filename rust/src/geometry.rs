@@ -1,19 +1,19 @@
 use three_d::{degrees, vec2, vec3, Angle, Vec2, Vec3};
 
-pub fn max3(x: f32, y: f32, z: f32) -> f32 {
+pub fn _max3(x: f32, y: f32, z: f32) -> f32 {
     x.max(y).max(z)
 }
 
-pub fn min3(x: f32, y: f32, z: f32) -> f32 {
+pub fn _min3(x: f32, y: f32, z: f32) -> f32 {
     x.min(y).min(z)
 }
 
-fn polar(turn: f32) -> Vec2 {
+fn _polar(turn: f32) -> Vec2 {
     let angle = degrees(turn * 360.0);
     vec2(angle.cos(), angle.sin())
 }
 
-pub fn polar_generator<F: Fn(Vec2, Vec2) -> Vec<Vec3>>(
+pub fn _polar_generator<F: Fn(Vec2, Vec2) -> Vec<Vec3>>(
     subdivisions: u32,
     start: f32,
     end: f32,
@@ -23,15 +23,15 @@ pub fn polar_generator<F: Fn(Vec2, Vec2) -> Vec<Vec3>>(
     let diff = end - start;
     for i in 0..subdivisions {
         let left_turn = (i as f32 / subdivisions as f32) * diff + start;
-        let left = polar(left_turn);
+        let left = _polar(left_turn);
         let right_turn = ((i + 1) as f32 / subdivisions as f32) * diff + start;
-        let right = polar(right_turn);
+        let right = _polar(right_turn);
         positions.extend(&func(left, right));
     }
     positions
 }
 
-pub fn unwrap_mesh(mesh: &Vec<Vec3>) -> Vec<Vec3> {
+pub fn _unwrap_mesh(mesh: &[Vec3]) -> Vec<Vec3> {
     let mut mesh: Vec<Vec3> = mesh
         .iter()
         .map(|pos| {
@@ -43,7 +43,7 @@ pub fn unwrap_mesh(mesh: &Vec<Vec3>) -> Vec<Vec3> {
     let angle = mesh[0].x;
     let mut latest = angle;
     for vec in mesh.iter_mut() {
-        vec.x = vec.x - angle;
+        vec.x -= angle;
         if latest - vec.x > 0.4 {
             vec.x += 1.0;
         }
@@ -52,8 +52,8 @@ pub fn unwrap_mesh(mesh: &Vec<Vec3>) -> Vec<Vec3> {
     mesh
 }
 
-pub fn tube_mesh(subdivisions: u32) -> Vec<Vec3> {
-    polar_generator(subdivisions, 0.0, 1.0, |left, right| {
+pub fn _tube_mesh(subdivisions: u32) -> Vec<Vec3> {
+    _polar_generator(subdivisions, 0.0, 1.0, |left, right| {
         let left_top = vec3(left.x, 1.0, -left.y);
         let right_top = vec3(right.x, 1.0, -right.y);
         let left_bottom = vec3(left.x, 0.0, -left.y);
@@ -69,50 +69,15 @@ pub fn tube_mesh(subdivisions: u32) -> Vec<Vec3> {
     })
 }
 
-pub fn _cylinder_mesh(subdivisions: u32) -> Vec<Vec3> {
-    polar_generator(subdivisions, 0.0, 1.0, |left, right| {
-        let top = vec3(0.0, 1.0, 0.0);
-        let left_top = vec3(left.x, 1.0, left.y);
-        let right_top = vec3(right.x, 1.0, right.y);
-        let bottom = vec3(0.0, 0.0, 0.0);
-        let left_bottom = vec3(left.x, 0.0, left.y);
-        let right_bottom = vec3(right.x, 0.0, right.y);
-        vec![
-            left_top,
-            top,
-            right_top,
-            left_bottom,
-            left_top,
-            right_bottom,
-            right_bottom,
-            left_top,
-            right_top,
-            right_bottom,
-            bottom,
-            left_bottom,
-        ]
-    })
-}
-
-pub fn _cone_mesh(subdivisions: u32) -> Vec<Vec3> {
-    polar_generator(subdivisions, 0.0, 1.0, |left, right| {
-        let top = vec3(0.0, 1.0, 0.0);
-        let bottom = vec3(0.0, 0.0, 0.0);
-        let left = vec3(left.x, 1.0, left.y);
-        let right = vec3(right.x, 1.0, right.y);
-        vec![top, left, right, left, bottom, right]
-    })
-}
-
 pub fn quad_mesh() -> Vec<Vec3> {
-    return vec![
+    vec![
         vec3(0.0, 0.0, 0.0),
         vec3(1.0, 1.0, 0.0),
         vec3(0.0, 1.0, 0.0),
         vec3(0.0, 0.0, 0.0),
         vec3(1.0, 0.0, 0.0),
         vec3(1.0, 1.0, 0.0),
-    ];
+    ]
 }
 
 pub fn _nonuniform_subdivided_quad_mesh(
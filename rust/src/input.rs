@@ -2,7 +2,19 @@ use std::rc::Rc;
 
 use three_d::{vec2, vec3, Camera, Vec2, Vec3};
 
-use crate::embed::{CylindricalEmbedding, Embedding, OkhsvEmbedding};
+use crate::{
+    element::{
+        colorspace::SpaceDisplay,
+        embedswitcher::{color_embeddings, space_embeddings},
+        histogram::HistogramDisplay,
+        isolines::IsoSource,
+        Shading,
+    },
+    embed::Embedding,
+    mesh::{clip::Plane, normals::Normals, CpuMesh},
+    photo::Photo,
+    pre_embed::Primitive,
+};
 
 pub struct InputState {
     pub mouse_pos: Vec2,
@@ -12,9 +24,26 @@ pub struct InputState {
     pub chunk: Vec3,
     pub camera: Camera,
     pub press: bool,
-    pub input: bool,
     pub color_embedding: Rc<dyn Embedding<Vec3>>,
     pub space_embedding: Rc<dyn Embedding<Vec3>>,
+    pub space_display: SpaceDisplay,
+    pub shading: Shading,
+    /// The normals `shading` lights the solid with.
+    pub normals: Normals,
+    /// The shape in input space drawn as the color solid.
+    pub solid: Rc<CpuMesh>,
+    /// Cuts the solid in space coordinates, capping the cut.
+    pub clip: Option<Plane>,
+    /// Draws contour lines of hue, lightness and chroma on the solid.
+    pub iso_lines: bool,
+    /// More contour lines, where the source is a multiple of the spacing.
+    pub extra_iso_lines: Option<(IsoSource, f32)>,
+    /// Drawn as a cloud of its pixel colors.
+    pub photo: Option<Rc<Photo>>,
+    /// Oklab colors shown as chips instead of steps from the selection, unless empty.
+    pub palette: Vec<Vec3>,
+    /// Draws where the colors of `photo` fall in the solid.
+    pub histogram: Option<HistogramDisplay>,
 }
 
 impl InputState {
@@ -27,9 +56,18 @@ impl InputState {
             chunk: vec3(1., 1., 1.),
             camera,
             press: false,
-            input: false,
-            color_embedding: Rc::new(OkhsvEmbedding {}),
-            space_embedding: Rc::new(CylindricalEmbedding {}),
+            color_embedding: color_embeddings()[0].1.clone(),
+            space_embedding: space_embeddings()[0].1.clone(),
+            space_display: SpaceDisplay::Color,
+            shading: Shading::None,
+            normals: Normals::Smooth,
+            solid: Rc::new(Primitive::Cube.mesh()),
+            clip: None,
+            iso_lines: false,
+            extra_iso_lines: None,
+            photo: None,
+            palette: Vec::new(),
+            histogram: None,
         }
     }
 }
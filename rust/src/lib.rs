@@ -2,30 +2,59 @@ extern crate console_error_panic_hook;
 extern crate wasm_bindgen;
 extern crate web_sys;
 
+use std::{cell::RefCell, rc::Rc};
+
 use camera::CustomController;
 use input::InputState;
 use scene::ColorScene;
 use winit::window::WindowBuilder;
 mod camera;
+mod command;
+mod css;
 mod element;
 mod embed;
 mod geometry;
 mod input;
 mod mesh;
+mod palette_file;
+mod photo;
 mod pre_embed;
+mod quantize;
+mod remap;
 mod renders;
 mod scene;
+mod shader;
 
 use three_d::{
     renderer::{control::Event, *},
-    FrameOutput, SurfaceSettings, Window,
+    FrameInput, FrameOutput, SurfaceSettings, Window,
 };
 use wasm_bindgen::prelude::*;
 #[cfg(target_arch = "wasm32")]
 use web_sys::HtmlCanvasElement;
 
+use crate::command::Command;
+use crate::css::{CssColor, CssSyntax};
+use crate::element::{
+    coloraxis::Axis,
+    colorspace::SpaceDisplay,
+    embedswitcher::{color_embeddings, embedding_name, space_embeddings, NamedEmbedding},
+    histogram::HistogramDisplay,
+    isolines::IsoSource,
+    Shading,
+};
+use crate::embed::{graph, oklab_to_srgb, srgb_to_oklab, AffineEmbedding, Embedding};
+use crate::mesh::{
+    clip::Plane, export::MeshFormat, normals::Normals, subdivide::Subdivision, CpuMesh,
+};
+use crate::palette_file::{parse_hex, NamedColor, PaletteFile, PaletteFormat};
+use crate::photo::Photo;
+use crate::pre_embed::Primitive;
+use crate::quantize::{dominant_colors, Quantizer};
+use crate::remap::{rgba, DeltaE, Dither, Remap};
 pub use crate::renders::{Renderer, Space};
 use crate::scene::{Scene, Target};
+use crate::shader::{ShaderBuilder, ShaderError, COLOR_VERT, DEFAULT_COLOR};
 
 #[wasm_bindgen]
 extern "C" {
@@ -35,9 +64,24 @@ extern "C" {
     fn log(s: &str);
 }
 
+/// Called with the input position whenever a click or hover selects a new one.
+type OnSelect = Box<dyn FnMut(Vec3)>;
+
 #[wasm_bindgen]
 pub struct ColorView {
-    window: Window,
+    // `render_loop` never returns on the web, so it only borrows the view and the
+    // frame state is shared with the loop. This keeps the other methods callable.
+    window: RefCell<Option<Window>>,
+    view: Rc<RefCell<ViewState>>,
+    /// Changes made through `ColorView` methods, applied at the start of the next frame. Kept
+    /// apart from the view so they can be queued while a frame holds it.
+    commands: Rc<RefCell<Vec<Command>>>,
+    /// Moved into the loop by `render_loop`, which calls it without holding the view.
+    on_select: RefCell<Option<OnSelect>>,
+}
+
+struct ViewState {
+    context: Context,
     // width: u32,
     // height: u32,
     control: CustomController,
@@ -48,23 +92,64 @@ pub struct ColorView {
     cylindrical_scene: ColorScene,
     pos_texture: Texture2D,
     depth_texture: DepthTexture2D,
-    on_select: Option<Box<dyn FnMut(Vec3)>>,
     // on_hover: Option<Box<dyn FnMut(f32, f32, f32) -> ()>>,
 }
 
-fn color_shader(string: &str) -> String {
-    include_str!("color.frag").replace("// REPLACE", string)
+/// Pixels of the loaded image read when looking for its dominant colors.
+const DOMINANT_SAMPLES: usize = 50_000;
+/// Most colors `dominant_colors` may look for.
+const MAX_DOMINANT_COLORS: usize = 256;
+
+/// Most triangles `subdivide_solid` may leave the color solid with, before refinement.
+const MAX_SOLID_TRIANGLES: u64 = 200_000;
+
+fn color_program(context: &Context, shader: &ShaderBuilder) -> Result<Program, ShaderError> {
+    let src = shader.build()?;
+    #[cfg(feature = "validate")]
+    shader::validate(COLOR_VERT, &src)?;
+    Program::from_source(context, COLOR_VERT, &src)
+        .map_err(|error| ShaderError::Invalid(error.to_string()))
+}
+
+fn find_embedding(
+    embeddings: &[NamedEmbedding],
+    name: &str,
+) -> Result<Rc<dyn Embedding<Vec3>>, JsValue> {
+    embeddings
+        .iter()
+        .find(|&&(other, _)| other == name)
+        .map(|(_, embedding)| embedding.clone())
+        .ok_or_else(|| JsValue::from(format!("unknown embedding {}", name)))
 }
 
-fn color_program(context: &Context, string: &str) -> Program {
-    let src = color_shader(string);
-    Program::from_source(&context, include_str!("color.vert"), &src).unwrap()
+fn diagnostics_to_js(error: &ShaderError) -> JsValue {
+    let diagnostics = js_sys::Array::new();
+    for diagnostic in shader::diagnostics(error) {
+        let object = js_sys::Object::new();
+        let line = match diagnostic.line {
+            Some(line) => JsValue::from(line),
+            None => JsValue::null(),
+        };
+        let _ = js_sys::Reflect::set(&object, &"line".into(), &line);
+        let _ = js_sys::Reflect::set(
+            &object,
+            &"inExpression".into(),
+            &JsValue::from(diagnostic.in_expression),
+        );
+        let _ = js_sys::Reflect::set(
+            &object,
+            &"message".into(),
+            &JsValue::from(diagnostic.message.as_str()),
+        );
+        diagnostics.push(&object);
+    }
+    diagnostics.into()
 }
 
 #[wasm_bindgen]
 impl ColorView {
     #[cfg(not(target_arch = "wasm32"))]
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32) -> Result<ColorView, JsValue> {
         let window_builder = winit::window::WindowBuilder::new()
             .with_title("winit window")
             .with_min_inner_size(winit::dpi::LogicalSize::new(width, height))
@@ -78,7 +163,7 @@ impl ColorView {
         width: u32,
         height: u32,
         callback: js_sys::Function,
-    ) -> Self {
+    ) -> Result<ColorView, JsValue> {
         std::panic::set_hook(Box::new(console_error_panic_hook::hook));
         let window_builder = match canvas.dyn_into::<HtmlCanvasElement>() {
             Ok(canvas) => {
@@ -89,7 +174,7 @@ impl ColorView {
             }
             _ => panic!("ColorView::new must be passed a canvas!"),
         };
-        let f: Box<dyn FnMut(Vec3)> = Box::new(move |v: Vec3| {
+        let f: OnSelect = Box::new(move |v: Vec3| {
             let this = JsValue::null();
             let _ = callback.call3(
                 &this,
@@ -106,8 +191,8 @@ impl ColorView {
         window_builder: WindowBuilder,
         width: u32,
         height: u32,
-        on_select: Option<Box<dyn FnMut(Vec3)>>,
-    ) -> ColorView {
+        on_select: Option<OnSelect>,
+    ) -> Result<ColorView, JsValue> {
         let event_loop = winit::event_loop::EventLoop::new();
         let winit_window = window_builder.build(&event_loop).unwrap();
         let window =
@@ -127,15 +212,19 @@ impl ColorView {
         );
         let control = CustomController::new(*camera.target(), 1.0, 100.0);
 
-        // let cylindrical_program =
-        //     color_program(&context, "color = vec4(hsv2rgb(xyz2hsv(pos.xyz)), 1.0);");
-        let cylindrical_program =
-            color_program(&context, "color = vec4(oklab_to_srgb(pos.xyz), 1.0);");
-        // let cylindrical_program =
-        //     color_program(&context, "color = vec4(linear_srgb_to_oklab(pos.yxz), 1.0);");
-        // let cylindrical_program = color_program(&context, "color = vec4(linear_srgb_to_srgb(pos.xyz), 1.0);");
+        // let cylindrical_program = color_program(
+        //     &context,
+        //     &ShaderBuilder::color("color = vec4(hsv2rgb(xyz2hsv(pos.xyz)), 1.0);"),
+        // );
+        let cylindrical_program = color_program(&context, &ShaderBuilder::color(DEFAULT_COLOR))
+            .map_err(|error| diagnostics_to_js(&error))?;
+        // let cylindrical_program = color_program(
+        //     &context,
+        //     &ShaderBuilder::color("color = vec4(oklab_to_srgb(pos.xyz), 1.0);").flag("MARK_GAMUT"),
+        // );
 
-        let pos_program = color_program(&context, "color = vec4(pos.xyz, tag);");
+        let pos_program = color_program(&context, &ShaderBuilder::pick())
+            .map_err(|error| diagnostics_to_js(&error))?;
         let pos_texture = Texture2D::new_empty::<[f32; 4]>(
             &context,
             width,
@@ -154,15 +243,14 @@ impl ColorView {
             Wrapping::ClampToEdge,
         );
         let state = InputState::new(vec3(1.0, 1.0, 1.0), camera);
-        let mut cylindrical_scene = ColorScene::new(&context);
+        let mut cylindrical_scene = ColorScene::new(&context, state.solid.clone());
         cylindrical_scene.update(&state);
-        let view = ColorView {
-            window,
+        let view = ViewState {
+            context,
             // width,
             // height,
             control,
             position: vec2(0.0, 0.0),
-            on_select,
             // on_hover: None,
             state,
             cylindrical_program,
@@ -171,66 +259,718 @@ impl ColorView {
             pos_texture,
             depth_texture,
         };
-        view
-    }
-
-    pub fn render_loop(mut self) {
-        self.window.render_loop(move |mut input| {
-            let mut press = false;
-            for event in input.events.iter() {
-                match event {
-                    Event::MouseMotion { position: pos, .. } => {
-                        self.position = Vec2::new(pos.x, pos.y);
-                    }
-                    Event::MousePress {
-                        button,
-                        position: pos,
-                        ..
-                    } => {
-                        self.position = Vec2::new(pos.x, pos.y);
-                        press = *button == MouseButton::Left;
-                    }
-                    _ => {}
+        Ok(ColorView {
+            window: RefCell::new(Some(window)),
+            view: Rc::new(RefCell::new(view)),
+            commands: Rc::new(RefCell::new(Vec::new())),
+            on_select: RefCell::new(on_select),
+        })
+    }
+
+    /// Replaces the color program with one running `expression`, e.g.
+    /// `color = vec4(oklab_to_srgb(pos.xyz), 1.0);`.
+    ///
+    /// On failure the previous program stays active and the compile errors are returned
+    /// as `{ line, inExpression, message }` objects, with lines relative to `expression`.
+    pub fn set_color_expression(&self, expression: &str) -> Result<(), JsValue> {
+        let program = color_program(
+            &self.view.borrow().context,
+            &ShaderBuilder::color(expression),
+        )
+        .map_err(|error| diagnostics_to_js(&error))?;
+        self.push(Command::ColorProgram(Box::new(program)));
+        Ok(())
+    }
+
+    /// Converts a position in the `from` color space to the `to` color space, e.g. from
+    /// `"okhsl"` (hue, lightness and saturation in `[0, 1]`) to `"display-p3"`.
+    pub fn convert_color(
+        from: &str,
+        to: &str,
+        x: f32,
+        y: f32,
+        z: f32,
+    ) -> Result<Vec<f32>, JsValue> {
+        let embedding = graph::convert(from, to)
+            .ok_or_else(|| JsValue::from(format!("no conversion from {} to {}", from, to)))?;
+        let color = embedding.embed(vec3(x, y, z));
+        Ok(vec![color.x, color.y, color.z])
+    }
+
+    /// Position in the `space` color space and alpha of a CSS color, e.g.
+    /// `parse_css_color("oklch(from #0af l c calc(h + 180))", "okhsl")`.
+    pub fn parse_css_color(css: &str, space: &str) -> Result<Vec<f32>, JsValue> {
+        let embedding = graph::convert(space, "oklab")
+            .ok_or_else(|| JsValue::from(format!("unknown color space {}", space)))?;
+        let color = css::parse(css).map_err(|e| JsValue::from(e.to_string()))?;
+        let pos = embedding.invert(color.oklab);
+        Ok(vec![pos.x, pos.y, pos.z, color.alpha])
+    }
+
+    /// Selects the color of a CSS color string under the active color embedding.
+    pub fn set_css_color(&self, css: &str) -> Result<(), JsValue> {
+        let color = css::parse(css).map_err(|e| JsValue::from(e.to_string()))?;
+        self.push(Command::Color(color.oklab));
+        Ok(())
+    }
+
+    /// The selected color as CSS, written as `"hex"`, with a color function such as `"oklch"`,
+    /// or with `color()` in a named space such as `"display-p3"`.
+    pub fn css_color(&self, syntax: &str) -> Result<String, JsValue> {
+        let syntax = CssSyntax::from_name(syntax).map_err(|e| JsValue::from(e.to_string()))?;
+        let view = self.view.borrow();
+        let color =
+            CssColor::from_position(view.state.saved_pos, view.state.color_embedding.as_ref());
+        Ok(css::serialize(&color, &syntax))
+    }
+
+    fn push(&self, command: Command) {
+        self.commands.borrow_mut().push(command);
+    }
+
+    /// The value `find` reads from the latest queued command, or else the one `current` reads
+    /// from the view, so setters called earlier in the same frame are seen.
+    fn pending<T>(
+        &self,
+        find: impl Fn(&Command) -> Option<T>,
+        current: impl FnOnce(&InputState) -> T,
+    ) -> T {
+        let queued = self.commands.borrow().iter().rev().find_map(find);
+        queued.unwrap_or_else(|| current(&self.view.borrow().state))
+    }
+
+    fn photo(&self) -> Option<Rc<Photo>> {
+        self.pending(
+            |command| match command {
+                Command::Photo(photo) => Some(photo.clone()),
+                _ => None,
+            },
+            |state| state.photo.clone(),
+        )
+    }
+
+    fn solid(&self) -> Rc<CpuMesh> {
+        self.pending(
+            |command| match command {
+                Command::Solid(solid) => Some(solid.clone()),
+                _ => None,
+            },
+            |state| state.solid.clone(),
+        )
+    }
+
+    fn palette(&self) -> Vec<Vec3> {
+        self.pending(
+            |command| match command {
+                Command::Palette(palette) => Some(palette.clone()),
+                _ => None,
+            },
+            |state| state.palette.clone(),
+        )
+    }
+
+    /// The selected input position of the color embedding, each axis in `[0, 1]`.
+    pub fn pos(&self) -> Vec<f32> {
+        let pos = self.view.borrow().state.saved_pos;
+        vec![pos.x, pos.y, pos.z]
+    }
+
+    /// Selects an input position on the next frame. Like the other setters, this does not call
+    /// the `on_select` callback.
+    pub fn set_pos(&self, x: f32, y: f32, z: f32) {
+        self.push(Command::Pos(vec3(x, y, z)));
+    }
+
+    /// The selected color in Oklab.
+    pub fn color(&self) -> Vec<f32> {
+        let view = self.view.borrow();
+        let color = view.state.color_embedding.embed(view.state.saved_pos);
+        vec![color.x, color.y, color.z]
+    }
+
+    /// Selects the position of an Oklab color under the active color embedding on the next
+    /// frame.
+    pub fn set_color(&self, l: f32, a: f32, b: f32) {
+        self.push(Command::Color(vec3(l, a, b)));
+    }
+
+    /// How far the solid is drawn along each input axis.
+    pub fn chunk(&self) -> Vec<f32> {
+        let chunk = self.view.borrow().state.chunk;
+        vec![chunk.x, chunk.y, chunk.z]
+    }
+
+    pub fn set_chunk(&self, x: f32, y: f32, z: f32) {
+        self.push(Command::Chunk(vec3(x, y, z)));
+    }
+
+    /// Identifiers accepted by `set_color_embedding`.
+    pub fn color_embeddings() -> Box<[JsValue]> {
+        color_embeddings()
+            .iter()
+            .map(|&(name, _)| JsValue::from(name))
+            .collect()
+    }
+
+    /// Identifier of the active color embedding.
+    pub fn color_embedding(&self) -> Option<String> {
+        let view = self.view.borrow();
+        embedding_name(&color_embeddings(), &view.state.color_embedding).map(str::to_string)
+    }
+
+    pub fn set_color_embedding(&self, name: &str) -> Result<(), JsValue> {
+        let embedding = find_embedding(&color_embeddings(), name)?;
+        self.push(Command::ColorEmbedding(embedding));
+        Ok(())
+    }
+
+    /// Identifiers accepted by `set_space_embedding`.
+    pub fn space_embeddings() -> Box<[JsValue]> {
+        space_embeddings()
+            .iter()
+            .map(|&(name, _)| JsValue::from(name))
+            .collect()
+    }
+
+    /// Identifier of the active space embedding.
+    pub fn space_embedding(&self) -> Option<String> {
+        let view = self.view.borrow();
+        embedding_name(&space_embeddings(), &view.state.space_embedding).map(str::to_string)
+    }
+
+    pub fn set_space_embedding(&self, name: &str) -> Result<(), JsValue> {
+        let embedding = find_embedding(&space_embeddings(), name)?;
+        self.push(Command::SpaceEmbedding(embedding));
+        Ok(())
+    }
+
+    /// Lays the color solid out with an affine or projective transform of Oklab, given as 16
+    /// column-major values like a WebGL matrix uniform.
+    pub fn set_space_matrix(&self, matrix: &[f32]) -> Result<(), JsValue> {
+        let columns: [[f32; 4]; 4] = match matrix {
+            &[a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p] => {
+                [[a, b, c, d], [e, f, g, h], [i, j, k, l], [m, n, o, p]]
+            }
+            _ => return Err(JsValue::from("space matrices need 16 values")),
+        };
+        let embedding = AffineEmbedding::new(Mat4::from(columns))
+            .ok_or_else(|| JsValue::from("space matrix can't be inverted"))?;
+        self.push(Command::SpaceEmbedding(Rc::new(embedding)));
+        Ok(())
+    }
+
+    /// The camera pose as `{ position, target, up }`, each an `[x, y, z]` array.
+    pub fn camera(&self) -> JsValue {
+        let view = self.view.borrow();
+        let camera = &view.state.camera;
+        let object = js_sys::Object::new();
+        let fields = [
+            ("position", camera.position()),
+            ("target", camera.target()),
+            ("up", camera.up()),
+        ];
+        for (name, v) in fields {
+            let array = js_sys::Array::of3(&v.x.into(), &v.y.into(), &v.z.into());
+            let _ = js_sys::Reflect::set(&object, &name.into(), &array);
+        }
+        object.into()
+    }
+
+    /// Moves the camera on the next frame. The camera then orbits and zooms around `target`.
+    pub fn set_camera(&self, position: &[f32], target: &[f32], up: &[f32]) -> Result<(), JsValue> {
+        let vector = |v: &[f32]| match v {
+            &[x, y, z] => Ok(vec3(x, y, z)),
+            _ => Err(JsValue::from("camera vectors need three components")),
+        };
+        self.push(Command::Camera {
+            position: vector(position)?,
+            target: vector(target)?,
+            up: vector(up)?,
+        });
+        Ok(())
+    }
+
+    /// Names of the color spaces `convert_color` understands.
+    pub fn color_spaces() -> Box<[JsValue]> {
+        graph::spaces()
+            .iter()
+            .map(|space| JsValue::from(space.as_str()))
+            .collect()
+    }
+
+    /// Names of the color spaces visited when converting from `from` to `to`.
+    pub fn conversion_path(from: &str, to: &str) -> Option<Box<[JsValue]>> {
+        let path = graph::path(from, to)?;
+        Some(
+            path.iter()
+                .map(|space| JsValue::from(space.as_str()))
+                .collect(),
+        )
+    }
+
+    /// The position in every space of `conversion_path(from, to)`, flattened to `x, y, z`
+    /// triples.
+    pub fn trace_conversion(
+        from: &str,
+        to: &str,
+        x: f32,
+        y: f32,
+        z: f32,
+    ) -> Result<Vec<f32>, JsValue> {
+        let embedding = graph::convert(from, to)
+            .ok_or_else(|| JsValue::from(format!("no conversion from {} to {}", from, to)))?;
+        Ok(embedding
+            .trace(vec3(x, y, z))
+            .iter()
+            .flat_map(|pos| [pos.x, pos.y, pos.z])
+            .collect())
+    }
+
+    /// Input axes of the active color embedding as `{ name, unit, min, max, periodic, value }`
+    /// objects, with the selected value in native units.
+    pub fn axes(&self) -> Box<[JsValue]> {
+        let view = self.view.borrow();
+        let pos = view.state.saved_pos;
+        let axes = view.state.color_embedding.axes();
+        axes.iter()
+            .zip([pos.x, pos.y, pos.z])
+            .map(|(axis, value)| {
+                let object = js_sys::Object::new();
+                let _ = js_sys::Reflect::set(&object, &"name".into(), &axis.name.into());
+                let _ = js_sys::Reflect::set(&object, &"unit".into(), &axis.unit.into());
+                let _ = js_sys::Reflect::set(&object, &"min".into(), &axis.range.0.into());
+                let _ = js_sys::Reflect::set(&object, &"max".into(), &axis.range.1.into());
+                let _ = js_sys::Reflect::set(&object, &"periodic".into(), &axis.periodic.into());
+                let _ =
+                    js_sys::Reflect::set(&object, &"value".into(), &axis.to_native(value).into());
+                object.into()
+            })
+            .collect()
+    }
+
+    /// Colors the solid by `"color"`, or by `"distortion"` to show how much the color changes
+    /// per step of the input.
+    pub fn set_display(&self, display: &str) -> Result<(), JsValue> {
+        let display = match display {
+            "color" => SpaceDisplay::Color,
+            "distortion" => SpaceDisplay::Distortion,
+            _ => return Err(JsValue::from(format!("unknown display {}", display))),
+        };
+        self.push(Command::Display(display));
+        Ok(())
+    }
+
+    /// Lights the color solid with `"diffuse"` or `"rim"` shading to show its shape, or shows
+    /// exact colors with `"none"`. Shading only scales linear light, so hues stay put.
+    pub fn set_shading(&self, shading: &str) -> Result<(), JsValue> {
+        let shading = match shading {
+            "none" => Shading::None,
+            "diffuse" => Shading::Diffuse,
+            "rim" => Shading::Rim,
+            _ => return Err(JsValue::from(format!("unknown shading {}", shading))),
+        };
+        self.push(Command::Shading(shading));
+        Ok(())
+    }
+
+    /// Lights the solid with `"smooth"` normals, or with `"face"` normals showing every
+    /// triangle.
+    pub fn set_normals(&self, normals: &str) -> Result<(), JsValue> {
+        let normals = match normals {
+            "face" => Normals::Face,
+            "smooth" => Normals::Smooth,
+            _ => return Err(JsValue::from(format!("unknown normals {}", normals))),
+        };
+        self.push(Command::Normals(normals));
+        Ok(())
+    }
+
+    /// Draws contour lines on the color solid: every 30° of input hue, every tenth of Oklab
+    /// lightness and every 0.05 of Oklab chroma.
+    pub fn set_iso_lines(&self, enabled: bool) {
+        self.push(Command::IsoLines(enabled));
+    }
+
+    /// Also draws contour lines of input `axis` (0, 1 or 2) of the color embedding named
+    /// `embedding` every `spacing`, e.g. `("okhsv", 0, 1 / 12)` for Okhsv hue on an Okhsl
+    /// solid. Without an embedding the extra lines are removed.
+    pub fn set_embedding_iso_lines(
+        &self,
+        embedding: Option<String>,
+        axis: u32,
+        spacing: f32,
+    ) -> Result<(), JsValue> {
+        let lines = match embedding {
+            Some(name) => {
+                let embedding = find_embedding(&color_embeddings(), &name)?;
+                let axis = match axis {
+                    0 => Axis::X,
+                    1 => Axis::Y,
+                    2 => Axis::Z,
+                    _ => return Err(JsValue::from(format!("no axis {}", axis))),
+                };
+                if spacing.is_nan() || spacing <= 0.0 {
+                    return Err(JsValue::from("iso-line spacing must be positive"));
                 }
+                Some((IsoSource::Embedding(embedding, axis), spacing))
             }
-            self.control
-                .handle_events(&mut self.state.camera, &mut input.events);
-            let screen = input.screen();
-            let state = &mut self.state;
-            state.mouse_pos = self.position;
-            state.press = press;
-            screen.clear(ClearState::color_and_depth(0.8, 0.8, 0.8, 0.0, 1.0));
-            let pos_target = RenderTarget::new(
-                self.pos_texture.as_color_target(None),
-                self.depth_texture.as_depth_target(),
-            );
-            pos_target.clear(ClearState::color_and_depth(0.0, 0.0, 0.0, 0.0, 1.0));
-
-            let program = &mut self.cylindrical_program;
-            let scene = &mut self.cylindrical_scene;
-            let mut target = Target {
-                target: &screen,
-                program,
-                pos_target: &pos_target,
-                pos_program: &mut self.pos_program,
-            };
-            let pos_state = state.pos;
-            scene.render(&mut target, state);
-
-            if press {
-                state.saved_pos = state.pos;
+            None => None,
+        };
+        self.push(Command::ExtraIsoLines(lines));
+        Ok(())
+    }
+
+    /// The color solid as drawn, in `"obj"`, `"ply"` or `"gltf"` (binary) format, with vertex
+    /// colors from the active color embedding.
+    pub fn export_mesh(&self, format: &str) -> Result<Vec<u8>, JsValue> {
+        let format = MeshFormat::from_name(format)
+            .ok_or_else(|| JsValue::from(format!("unknown mesh format {}", format)))?;
+        let view = self.view.borrow();
+        let mesh = view
+            .cylindrical_scene
+            .export()
+            .ok_or_else(|| JsValue::from("nothing to export"))?;
+        Ok(mesh.export(format))
+    }
+
+    /// Replaces the color solid with an `"obj"` or `"ply"` mesh whose positions are in input
+    /// space, so `[0, 1]` on every axis covers the whole color space.
+    pub fn load_mesh(&self, bytes: &[u8], format: &str) -> Result<(), JsValue> {
+        let format = MeshFormat::from_name(format)
+            .ok_or_else(|| JsValue::from(format!("unknown mesh format {}", format)))?;
+        let mesh =
+            CpuMesh::import(bytes, format).map_err(|error| JsValue::from(error.to_string()))?;
+        self.push(Command::Solid(Rc::new(mesh.clean(1e-6))));
+        Ok(())
+    }
+
+    /// Shows where the pixels of a PNG or JPEG image sit in the color solid. Picking a point
+    /// selects its color.
+    pub fn load_image(&self, bytes: &[u8]) -> Result<(), JsValue> {
+        let photo = Photo::decode(bytes).map_err(|error| JsValue::from(error.to_string()))?;
+        self.push(Command::Photo(Some(Rc::new(photo))));
+        Ok(())
+    }
+
+    pub fn clear_image(&self) {
+        self.push(Command::Photo(None));
+    }
+
+    /// Draws where the colors of the loaded image fall in the solid as `"voxels"` sized by
+    /// their pixel count, or as `"surfaces"` around its denser regions. `"off"` hides them.
+    pub fn set_histogram(&self, display: &str) -> Result<(), JsValue> {
+        let display = match display {
+            "off" => None,
+            "voxels" => Some(HistogramDisplay::Voxels),
+            "surfaces" => Some(HistogramDisplay::Surfaces),
+            _ => {
+                return Err(JsValue::from(format!(
+                    "unknown histogram display {}",
+                    display
+                )))
             }
-            if state.pos != pos_state {
-                log(&format!(
-                    "before: {:?} after: {:?}, chunk: {:?}",
-                    pos_state, state.pos, state.chunk
-                ));
-                if let Some(on_select) = self.on_select.as_mut() {
-                    on_select(state.pos);
-                }
+        };
+        self.push(Command::Histogram(display));
+        Ok(())
+    }
+
+    /// The `count` dominant colors of the loaded image as `{ oklab, srgb, weight }` objects,
+    /// heaviest first, with `weight` the fraction of pixels each stands for.
+    ///
+    /// `method` is `"kmeans"`, `"median-cut"` or `"octree"`, grouping colors by distance in the
+    /// `space` color space, e.g. `"oklab"`. With `show` the colors replace the color chips.
+    /// At most 256 colors are looked for.
+    pub fn dominant_colors(
+        &self,
+        count: usize,
+        method: &str,
+        space: &str,
+        show: bool,
+    ) -> Result<Box<[JsValue]>, JsValue> {
+        if count > MAX_DOMINANT_COLORS {
+            return Err(JsValue::from("too many dominant colors"));
+        }
+        let quantizer = Quantizer::from_name(method)
+            .ok_or_else(|| JsValue::from(format!("unknown method {}", method)))?;
+        let embedding = graph::convert(space, "oklab")
+            .ok_or_else(|| JsValue::from(format!("unknown color space {}", space)))?;
+        let photo = self
+            .photo()
+            .ok_or_else(|| JsValue::from("no image loaded"))?;
+        let swatches = dominant_colors(
+            &photo.sample(DOMINANT_SAMPLES),
+            count,
+            quantizer,
+            embedding.as_ref(),
+        );
+        if show {
+            self.push(Command::Palette(
+                swatches.iter().map(|swatch| swatch.color).collect(),
+            ));
+        }
+        Ok(swatches
+            .iter()
+            .map(|swatch| {
+                let object = js_sys::Object::new();
+                let oklab = swatch.color;
+                let srgb = oklab_to_srgb(oklab);
+                let oklab = js_sys::Array::of3(&oklab.x.into(), &oklab.y.into(), &oklab.z.into());
+                let srgb = js_sys::Array::of3(&srgb.x.into(), &srgb.y.into(), &srgb.z.into());
+                let _ = js_sys::Reflect::set(&object, &"oklab".into(), &oklab);
+                let _ = js_sys::Reflect::set(&object, &"srgb".into(), &srgb);
+                let _ = js_sys::Reflect::set(&object, &"weight".into(), &swatch.weight.into());
+                object.into()
+            })
+            .collect())
+    }
+
+    /// The loaded image drawn with only the shown palette colors, as RGBA bytes for an
+    /// `ImageData` of `image_size()`.
+    ///
+    /// Pixels take the nearest color by the `"ok"`, `"cie76"` or `"ciede2000"` color
+    /// difference, dithered with `"none"`, `"floyd-steinberg"`, `"bayer"` or `"blue-noise"`.
+    pub fn remap_image(&self, metric: &str, dither: &str) -> Result<Vec<u8>, JsValue> {
+        let metric = DeltaE::from_name(metric)
+            .ok_or_else(|| JsValue::from(format!("unknown color difference {}", metric)))?;
+        let dither = Dither::from_name(dither)
+            .ok_or_else(|| JsValue::from(format!("unknown dither {}", dither)))?;
+        let photo = self
+            .photo()
+            .ok_or_else(|| JsValue::from("no image loaded"))?;
+        let palette = self.palette();
+        if palette.is_empty() {
+            return Err(JsValue::from("no palette shown"));
+        }
+        let remap = Remap::new(&palette, metric);
+        Ok(rgba(&remap.photo(&photo, dither)))
+    }
+
+    /// Width and height of the loaded image.
+    pub fn image_size(&self) -> Option<Vec<u32>> {
+        let photo = self.photo()?;
+        Some(vec![photo.width, photo.height])
+    }
+
+    /// Goes back to color chips stepping from the selection.
+    pub fn clear_palette(&self) {
+        self.push(Command::Palette(Vec::new()));
+    }
+
+    /// Shows `colors`, given as hex strings, as the palette in place of the color chips.
+    pub fn set_palette(&self, colors: Vec<JsValue>) -> Result<(), JsValue> {
+        let palette = colors
+            .iter()
+            .map(|color| {
+                let hex = color.as_string().unwrap_or_default();
+                parse_hex(&hex)
+                    .map(srgb_to_oklab)
+                    .ok_or_else(|| JsValue::from(format!("not a hex color: {}", hex)))
+            })
+            .collect::<Result<_, _>>()?;
+        self.push(Command::Palette(palette));
+        Ok(())
+    }
+
+    /// Colors of a palette file as `{ name, hex }` objects, `name` being `undefined` for
+    /// unnamed colors.
+    ///
+    /// The format is `"gpl"`, `"ase"`, `"aco"`, `"css"` (custom properties), `"tailwind"` (a
+    /// config as JSON) or `"json"` (an array of `{ name, hex }`).
+    pub fn read_palette(bytes: &[u8], format: &str) -> Result<Box<[JsValue]>, JsValue> {
+        let format = PaletteFormat::from_name(format)
+            .ok_or_else(|| JsValue::from(format!("unknown palette format {}", format)))?;
+        let palette = PaletteFile::read(bytes, format).map_err(|e| JsValue::from(e.to_string()))?;
+        Ok(palette
+            .colors
+            .iter()
+            .map(|color| {
+                let object = js_sys::Object::new();
+                let name = color
+                    .name
+                    .as_deref()
+                    .map_or(JsValue::UNDEFINED, JsValue::from);
+                let _ = js_sys::Reflect::set(&object, &"name".into(), &name);
+                let _ = js_sys::Reflect::set(&object, &"hex".into(), &color.hex().into());
+                object.into()
+            })
+            .collect())
+    }
+
+    /// Hex `colors` with their `names`, written as a palette file in `format` as for
+    /// `read_palette`. Missing names are made up from the position of the color.
+    pub fn write_palette(
+        colors: Vec<JsValue>,
+        names: Vec<JsValue>,
+        format: &str,
+    ) -> Result<Vec<u8>, JsValue> {
+        let format = PaletteFormat::from_name(format)
+            .ok_or_else(|| JsValue::from(format!("unknown palette format {}", format)))?;
+        let colors = colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| {
+                let hex = color.as_string().unwrap_or_default();
+                let srgb = parse_hex(&hex)
+                    .ok_or_else(|| JsValue::from(format!("not a hex color: {}", hex)))?;
+                let name = names.get(i).and_then(JsValue::as_string);
+                Ok(NamedColor::new(name, srgb))
+            })
+            .collect::<Result<_, JsValue>>()?;
+        Ok(PaletteFile::new(None, colors).write(format))
+    }
+
+    /// Replaces the color solid with a `"cube"`, `"cylinder"`, `"cone"`, `"bicone"`, `"sphere"`
+    /// or `"hemisphere"`, shaped for cylindrical spaces with hue around the axis.
+    pub fn set_solid(&self, name: &str) -> Result<(), JsValue> {
+        let primitive = Primitive::from_name(name)
+            .ok_or_else(|| JsValue::from(format!("unknown solid {}", name)))?;
+        self.push(Command::Solid(Rc::new(primitive.mesh())));
+        Ok(())
+    }
+
+    /// Subdivides the color solid `passes` times, `"linear"` keeping its shape and `"loop"`
+    /// rounding it off.
+    pub fn subdivide_solid(&self, scheme: &str, passes: u32) -> Result<(), JsValue> {
+        let scheme = Subdivision::from_name(scheme)
+            .ok_or_else(|| JsValue::from(format!("unknown subdivision {}", scheme)))?;
+        // Builds on a solid set earlier in the same frame
+        let solid = self.solid();
+        let triangles = (solid.indices.len() / 3) as u64;
+        if passes > 8 || triangles << (2 * passes) > MAX_SOLID_TRIANGLES {
+            return Err(JsValue::from("too many subdivision passes"));
+        }
+        self.push(Command::Solid(Rc::new(solid.subdivide_n(scheme, passes))));
+        Ok(())
+    }
+
+    /// How the triangles of the color solid connect, as `{ vertices, triangles, boundaryEdges,
+    /// nonManifoldEdges, inconsistentEdges, manifold, watertight }`.
+    pub fn solid_topology(&self) -> JsValue {
+        let topology = self.view.borrow().state.solid.topology();
+        let object = js_sys::Object::new();
+        let fields: [(&str, JsValue); 7] = [
+            ("vertices", topology.vertices.into()),
+            ("triangles", topology.triangles.into()),
+            ("boundaryEdges", topology.boundary_edges.into()),
+            ("nonManifoldEdges", topology.non_manifold_edges.into()),
+            ("inconsistentEdges", topology.inconsistent_edges.into()),
+            ("manifold", topology.is_manifold().into()),
+            ("watertight", topology.is_watertight().into()),
+        ];
+        for (name, value) in fields {
+            let _ = js_sys::Reflect::set(&object, &name.into(), &value);
+        }
+        object.into()
+    }
+
+    /// The holes in the color solid, as input space positions of the ends of every boundary
+    /// edge flattened to `x0, y0, z0, x1, y1, z1` runs.
+    pub fn solid_boundary(&self) -> Vec<f32> {
+        let view = self.view.borrow();
+        let solid = &view.state.solid;
+        solid
+            .boundary_edges()
+            .into_iter()
+            .flat_map(|(a, b)| [solid.positions[a as usize], solid.positions[b as usize]])
+            .flat_map(|pos| [pos.x, pos.y, pos.z])
+            .collect()
+    }
+
+    /// Cuts the color solid, keeping the space coordinates `p` where `normal · p >= offset`,
+    /// and fills the cut with the colors of the section, e.g. `(0, -1, 0, -0.5)` shows the
+    /// lightness 0.5 slice of the Oklab space. The coordinates are those drawn, so the cut
+    /// follows the chunk as it scales the solid.
+    pub fn set_clip_plane(&self, nx: f32, ny: f32, nz: f32, offset: f32) {
+        self.push(Command::Clip(Some(Plane::new(vec3(nx, ny, nz), offset))));
+    }
+
+    pub fn clear_clip_plane(&self) {
+        self.push(Command::Clip(None));
+    }
+
+    pub fn render_loop(&self) {
+        let window = match self.window.borrow_mut().take() {
+            Some(window) => window,
+            None => return,
+        };
+        let view = self.view.clone();
+        let commands = self.commands.clone();
+        let mut on_select = self.on_select.borrow_mut().take();
+        window.render_loop(move |mut input| {
+            let commands = std::mem::take(&mut *commands.borrow_mut());
+            let selected = view.borrow_mut().frame(commands, &mut input);
+            // The view is released first, so the callback may use the getters and setters
+            if let (Some(pos), Some(on_select)) = (selected, on_select.as_mut()) {
+                on_select(pos);
             }
-            scene.update(state);
             FrameOutput::default()
         });
     }
 }
+
+impl ViewState {
+    /// Applies `commands`, handles the input and draws a frame. Returns the newly selected
+    /// position, if any.
+    fn frame(&mut self, commands: Vec<Command>, input: &mut FrameInput) -> Option<Vec3> {
+        for command in commands {
+            command.apply(
+                &mut self.state,
+                &mut self.control,
+                &mut self.cylindrical_program,
+            );
+        }
+        let mut press = false;
+        for event in input.events.iter() {
+            match event {
+                Event::MouseMotion { position: pos, .. } => {
+                    self.position = Vec2::new(pos.x, pos.y);
+                }
+                Event::MousePress {
+                    button,
+                    position: pos,
+                    ..
+                } => {
+                    self.position = Vec2::new(pos.x, pos.y);
+                    press = *button == MouseButton::Left;
+                }
+                _ => {}
+            }
+        }
+        self.control
+            .handle_events(&mut self.state.camera, &mut input.events);
+        let screen = input.screen();
+        let state = &mut self.state;
+        state.mouse_pos = self.position;
+        state.press = press;
+        screen.clear(ClearState::color_and_depth(0.8, 0.8, 0.8, 0.0, 1.0));
+        let pos_target = RenderTarget::new(
+            self.pos_texture.as_color_target(None),
+            self.depth_texture.as_depth_target(),
+        );
+        pos_target.clear(ClearState::color_and_depth(0.0, 0.0, 0.0, 0.0, 1.0));
+
+        let program = &mut self.cylindrical_program;
+        let scene = &mut self.cylindrical_scene;
+        let mut target = Target {
+            target: &screen,
+            program,
+            pos_target: &pos_target,
+            pos_program: &mut self.pos_program,
+        };
+        let pos_state = state.pos;
+        scene.render(&mut target, state);
+
+        if press {
+            state.saved_pos = state.pos;
+        }
+        let selected = (state.pos != pos_state).then_some(state.pos);
+        if selected.is_some() {
+            log(&format!(
+                "before: {:?} after: {:?}, chunk: {:?}",
+                pos_state, state.pos, state.chunk
+            ));
+        }
+        scene.update(state);
+        selected
+    }
+}
@@ -1,12 +1,27 @@
+pub mod attribute;
+pub mod clip;
+pub mod export;
 pub mod geometry;
+pub mod import;
+pub mod lines;
+pub mod normals;
+pub mod subdivide;
+pub mod topology;
+
+use std::collections::HashMap;
 
 use cgmath::InnerSpace;
 use three_d::{Context, ElementBuffer, Vec3, VertexBuffer};
 
+use attribute::{Attribute, AttributeData};
+use normals::Normals;
+
 #[derive(Clone)]
 pub struct CpuMesh {
     pub positions: Vec<Vec3>,
     pub indices: Vec<u32>,
+    /// Extra channels with one value per position.
+    pub attributes: HashMap<Attribute, AttributeData>,
 }
 
 impl CpuMesh {
@@ -15,13 +30,64 @@ impl CpuMesh {
             Some(indices) => indices,
             None => Vec::from_iter(0..positions.len() as u32),
         };
-        Self { positions, indices }
+        Self {
+            positions,
+            indices,
+            attributes: HashMap::new(),
+        }
+    }
+
+    pub fn with_attribute(mut self, attribute: Attribute, data: AttributeData) -> Self {
+        self.set_attribute(attribute, data);
+        self
+    }
+
+    pub fn set_attribute(&mut self, attribute: Attribute, data: AttributeData) {
+        debug_assert_eq!(data.len(), self.positions.len());
+        self.attributes.insert(attribute, data);
+    }
+
+    pub fn attribute(&self, attribute: Attribute) -> Option<&AttributeData> {
+        self.attributes.get(&attribute)
+    }
+
+    /// Appends vertices mixed from the vertices `a` and `b` for every `(a, b, t)`, positions
+    /// included.
+    pub fn extend_mixed(&mut self, mixes: &[(u32, u32, f32)]) {
+        for &(a, b, t) in mixes {
+            let pos = self.positions[a as usize] * (1.0 - t) + self.positions[b as usize] * t;
+            self.positions.push(pos);
+        }
+        self.extend_attributes_mixed(mixes);
+    }
+
+    /// Appends mixed values to the attributes only, for positions placed some other way.
+    pub fn extend_attributes_mixed(&mut self, mixes: &[(u32, u32, f32)]) {
+        for data in self.attributes.values_mut() {
+            data.extend_mixed(mixes);
+        }
+    }
+
+    /// A copy keeping the vertices at `indices`, in order, with the given triangles.
+    pub fn gather(&self, indices: &[u32], triangles: Vec<u32>) -> Self {
+        Self {
+            positions: indices
+                .iter()
+                .map(|&i| self.positions[i as usize])
+                .collect(),
+            indices: triangles,
+            attributes: self
+                .attributes
+                .iter()
+                .map(|(&attribute, data)| (attribute, data.gather(indices)))
+                .collect(),
+        }
     }
 
     pub fn face(&mut self, point: Vec3, away: bool) {
         for i in 0..self.indices.len() / 3 {
             let i = i * 3;
-            let ia = self.indices[i + 0] as usize;
+            let ia = self.indices[i] as usize;
             let ib = self.indices[i + 1] as usize;
             let ic = self.indices[i + 2] as usize;
             let a = self.positions[ia];
@@ -43,53 +109,23 @@ impl CpuMesh {
         self.face(point, true)
     }
 
-    pub fn _subdivide(&self) -> Self {
-        let mut new_positions = self.positions.clone();
-        let mut new_indices = Vec::new();
-        for i in 0..self.indices.len() / 3 {
-            let i = i * 3;
-            let ia = self.indices[i + 0];
-            let ib = self.indices[i + 1];
-            let ic = self.indices[i + 2];
-            let a = self.positions[ia as usize];
-            let b = self.positions[ib as usize];
-            let c = self.positions[ic as usize];
-            let ab = (a + b) / 2.0;
-            let bc = (b + c) / 2.0;
-            let ca = (c + a) / 2.0;
-            let iab = new_positions.len() as u32;
-            let ibc = iab + 1;
-            let ica = ibc + 1;
-            new_positions.extend(&[ab, bc, ca]);
-            new_indices.extend(&[ia, iab, ica, ib, ibc, iab, ic, ica, ibc, iab, ibc, ica]);
-        }
-        Self {
-            positions: new_positions,
-            indices: new_indices,
-        }
-    }
-
-    pub fn _subdivide_n(&self, n: u32) -> Self {
-        let mut mesh = self.clone();
-        for _ in 0..n {
-            mesh = mesh._subdivide();
-        }
-        mesh
-    }
-
     pub fn split_triangles(&self) -> Self {
-        Self {
-            positions: self
-                .indices
-                .iter()
-                .map(|&i| self.positions[i as usize])
-                .collect(),
-            indices: Vec::from_iter(0..self.indices.len() as u32),
-        }
+        self.gather(&self.indices, Vec::from_iter(0..self.indices.len() as u32))
     }
 
+    /// Appends `other`. Attributes missing from either mesh are dropped, unless this one is empty.
     pub fn extend(&mut self, other: &Self) {
         let offset = self.positions.len() as u32;
+        if offset == 0 {
+            self.attributes = other.attributes.clone();
+        } else {
+            self.attributes.retain(|attribute, data| {
+                other
+                    .attributes
+                    .get(attribute)
+                    .is_some_and(|other| data.append(other))
+            });
+        }
         self.positions.extend(&other.positions);
         self.indices
             .extend(other.indices.iter().map(|&i| i + offset));
@@ -99,30 +135,81 @@ impl CpuMesh {
 pub struct GpuMesh {
     pub positions: VertexBuffer,
     pub indices: ElementBuffer,
+    pub attributes: HashMap<Attribute, VertexBuffer>,
+    context: Context,
 }
 
 impl GpuMesh {
     fn new(context: &Context, cpu_mesh: &CpuMesh) -> Self {
         let positions = VertexBuffer::new_with_data(context, &cpu_mesh.positions);
         let indices = ElementBuffer::new_with_data(context, &cpu_mesh.indices);
-        GpuMesh { positions, indices }
+        let attributes = cpu_mesh
+            .attributes
+            .iter()
+            .map(|(&attribute, data)| (attribute, data.buffer(context)))
+            .collect();
+        GpuMesh {
+            positions,
+            indices,
+            attributes,
+            context: context.clone(),
+        }
     }
 
     fn fill(&mut self, mesh: &CpuMesh) {
         self.positions.fill(&mesh.positions);
         self.indices.fill(&mesh.indices);
+        self.attributes
+            .retain(|attribute, _| mesh.attributes.contains_key(attribute));
+        for (&attribute, data) in &mesh.attributes {
+            self.fill_attribute(attribute, data);
+        }
+    }
+
+    fn fill_attribute(&mut self, attribute: Attribute, data: &AttributeData) {
+        match self.attributes.get_mut(&attribute) {
+            Some(buffer) => data.fill(buffer),
+            None => {
+                self.attributes
+                    .insert(attribute, data.buffer(&self.context));
+            }
+        }
     }
 }
 
 pub struct Mesh {
     pub cpu_mesh: CpuMesh,
     pub gpu_mesh: GpuMesh,
+    /// Kept in the `Normal` attribute whenever the positions change.
+    normals: Option<Normals>,
 }
 
 impl Mesh {
     pub fn new(context: &Context, cpu_mesh: CpuMesh) -> Self {
         let gpu_mesh = GpuMesh::new(context, &cpu_mesh);
-        Self { cpu_mesh, gpu_mesh }
+        Self {
+            cpu_mesh,
+            gpu_mesh,
+            normals: None,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: Normals) -> Self {
+        self.set_normals(normals);
+        self
+    }
+
+    /// Keeps a normal attribute of the given kind, recomputed whenever the mesh changes.
+    pub fn set_normals(&mut self, normals: Normals) {
+        self.normals = Some(normals);
+        self.update_normals();
+    }
+
+    fn update_normals(&mut self) {
+        if let Some(normals) = self.normals {
+            let normals = self.cpu_mesh.normals(normals);
+            self.set_attribute(Attribute::Normal, AttributeData::Vec3(normals));
+        }
     }
 
     pub fn from_mesh(context: &Context, mesh: &Mesh) -> Self {
@@ -152,9 +239,32 @@ impl Mesh {
         Self::new(context, cpu_mesh)
     }
 
-    pub fn _fill(&mut self, cpu_mesh: &CpuMesh) {
+    pub fn fill(&mut self, cpu_mesh: &CpuMesh) {
         self.cpu_mesh = cpu_mesh.clone();
         self.gpu_mesh.fill(cpu_mesh);
+        self.update_normals();
+    }
+
+    pub fn set_attribute(&mut self, attribute: Attribute, data: AttributeData) {
+        self.gpu_mesh.fill_attribute(attribute, &data);
+        self.cpu_mesh.set_attribute(attribute, data);
+    }
+
+    pub fn attribute(&self, attribute: Attribute) -> Option<&AttributeData> {
+        self.cpu_mesh.attribute(attribute)
+    }
+
+    pub fn attribute_buffer(&self, attribute: Attribute) -> Option<&VertexBuffer> {
+        self.gpu_mesh.attributes.get(&attribute)
+    }
+
+    /// Every attribute buffer, for binding them all in a `ColorModel`.
+    pub fn attribute_buffers(&self) -> Vec<(Attribute, &VertexBuffer)> {
+        self.gpu_mesh
+            .attributes
+            .iter()
+            .map(|(&attribute, buffer)| (attribute, buffer))
+            .collect()
     }
 
     pub fn embed<F>(&mut self, f: F)
@@ -166,6 +276,7 @@ impl Mesh {
             .iter_mut()
             .for_each(|pos| *pos = f(*pos));
         self.gpu_mesh.positions.fill(&self.cpu_mesh.positions);
+        self.update_normals();
     }
 
     pub fn _embed_from<F>(&mut self, mesh: &Mesh, f: F)
@@ -177,12 +288,13 @@ impl Mesh {
         self.gpu_mesh.fill(&self.cpu_mesh);
     }
 
-    pub fn embed_from_positions<F>(&mut self, positions: &Vec<Vec3>, f: F)
+    pub fn embed_from_positions<F>(&mut self, positions: &[Vec3], f: F)
     where
         F: Fn(Vec3) -> Vec3,
     {
         self.cpu_mesh.positions = positions.iter().map(|pos| f(*pos)).collect();
         self.gpu_mesh.fill(&self.cpu_mesh);
+        self.update_normals();
     }
 
     pub fn _embed_from_triangles<F>(&mut self, mesh: &Mesh, f: F)
@@ -192,8 +304,7 @@ impl Mesh {
         let prev = mesh.positions().clone();
         self.cpu_mesh.positions = prev
             .chunks(3)
-            .map(|chunk| f([chunk[0], chunk[1], chunk[2]]))
-            .flatten()
+            .flat_map(|chunk| f([chunk[0], chunk[1], chunk[2]]))
             .collect();
         self.cpu_mesh.indices = mesh._indices().clone();
         self.gpu_mesh.fill(&self.cpu_mesh);
@@ -0,0 +1,117 @@
+use three_d::{Context, Vec2, Vec3, VertexBuffer};
+
+/// Per-vertex channels a mesh can carry next to its positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Attribute {
+    Normal,
+    Uv,
+    /// Position in the input space of the embeddings.
+    Input,
+    /// Oklab color.
+    Color,
+    Tag,
+    /// The other end of a line quad's segment.
+    LineEnd,
+    /// Where a vertex sits in its line or point quad: `x` is the side of a line, in half widths,
+    /// and `y` runs from 0 to 1 along it. Points use both as half sizes.
+    Corner,
+}
+
+impl Attribute {
+    /// The shader input the attribute is bound to.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Uv => "uv",
+            Self::Input => "input_pos",
+            Self::Color => "vertex_color",
+            Self::Tag => "vertex_tag",
+            Self::LineEnd => "line_end",
+            Self::Corner => "corner",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeData {
+    Float(Vec<f32>),
+    Vec2(Vec<Vec2>),
+    Vec3(Vec<Vec3>),
+}
+
+impl AttributeData {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Float(data) => data.len(),
+            Self::Vec2(data) => data.len(),
+            Self::Vec3(data) => data.len(),
+        }
+    }
+
+    pub fn as_vec3(&self) -> Option<&Vec<Vec3>> {
+        match self {
+            Self::Vec3(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The values at `indices`, in order.
+    pub fn gather(&self, indices: &[u32]) -> Self {
+        fn gather<T: Copy>(data: &[T], indices: &[u32]) -> Vec<T> {
+            indices.iter().map(|&i| data[i as usize]).collect()
+        }
+        match self {
+            Self::Float(data) => Self::Float(gather(data, indices)),
+            Self::Vec2(data) => Self::Vec2(gather(data, indices)),
+            Self::Vec3(data) => Self::Vec3(gather(data, indices)),
+        }
+    }
+
+    /// Appends a value mixed from the values at `a` and `b` for every `(a, b, t)`.
+    pub fn extend_mixed(&mut self, mixes: &[(u32, u32, f32)]) {
+        match self {
+            Self::Float(data) => {
+                for &(a, b, t) in mixes {
+                    data.push(data[a as usize] * (1.0 - t) + data[b as usize] * t);
+                }
+            }
+            Self::Vec2(data) => {
+                for &(a, b, t) in mixes {
+                    data.push(data[a as usize] * (1.0 - t) + data[b as usize] * t);
+                }
+            }
+            Self::Vec3(data) => {
+                for &(a, b, t) in mixes {
+                    data.push(data[a as usize] * (1.0 - t) + data[b as usize] * t);
+                }
+            }
+        }
+    }
+
+    /// Appends `other` if it holds the same kind of values.
+    pub fn append(&mut self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Float(data), Self::Float(other)) => data.extend(other),
+            (Self::Vec2(data), Self::Vec2(other)) => data.extend(other),
+            (Self::Vec3(data), Self::Vec3(other)) => data.extend(other),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn buffer(&self, context: &Context) -> VertexBuffer {
+        match self {
+            Self::Float(data) => VertexBuffer::new_with_data(context, data),
+            Self::Vec2(data) => VertexBuffer::new_with_data(context, data),
+            Self::Vec3(data) => VertexBuffer::new_with_data(context, data),
+        }
+    }
+
+    pub fn fill(&self, buffer: &mut VertexBuffer) {
+        match self {
+            Self::Float(data) => buffer.fill(data),
+            Self::Vec2(data) => buffer.fill(data),
+            Self::Vec3(data) => buffer.fill(data),
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+
+use cgmath::InnerSpace;
+use three_d::{vec2, Vec2, Vec3};
+
+use crate::embed::Embedding;
+
+use super::{subdivide::edge_key, CpuMesh};
+
+/// Keeps the points where `normal · p >= offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub offset: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vec3, offset: f32) -> Self {
+        Self { normal, offset }
+    }
+
+    pub fn distance(&self, pos: Vec3) -> f32 {
+        self.normal.dot(pos) - self.offset
+    }
+
+    /// Two directions spanning the plane.
+    fn basis(&self) -> (Vec3, Vec3) {
+        let normal = self.normal.normalize();
+        let helper = if normal.x.abs() < 0.9 {
+            Vec3::unit_x()
+        } else {
+            Vec3::unit_y()
+        };
+        let u = normal.cross(helper).normalize();
+        (u, normal.cross(u))
+    }
+}
+
+/// Cut points closer than this in embedded space are the same point on the cap outline.
+const WELD: f32 = 1e-4;
+
+/// A point on the cap outline, rounded to `WELD`.
+type WeldKey = (i32, i32, i32);
+
+/// Cap outline segments by the point they start at. An outline touching itself, e.g. along the
+/// seam of a cylindrical embedding, starts several segments at one point.
+type Outline = HashMap<WeldKey, Vec<(WeldKey, Vec3)>>;
+
+/// Takes one of the segments starting at `key` off the outline.
+fn take_segment(outline: &mut Outline, key: WeldKey) -> Option<(WeldKey, Vec3)> {
+    let segments = outline.get_mut(&key)?;
+    let segment = segments.pop();
+    if segments.is_empty() {
+        outline.remove(&key);
+    }
+    segment
+}
+
+fn weld_key(pos: Vec3) -> WeldKey {
+    (
+        (pos.x / WELD).round() as i32,
+        (pos.y / WELD).round() as i32,
+        (pos.z / WELD).round() as i32,
+    )
+}
+
+/// Finds how far from `inside` to `outside` the embedding crosses `plane`.
+///
+/// The embedding bends, so the crossing is found by bisection rather than interpolating the
+/// distances of the ends.
+fn crossing(embedding: &dyn Embedding<Vec3>, plane: &Plane, inside: Vec3, outside: Vec3) -> f32 {
+    let (mut a, mut b) = (0.0, 1.0);
+    for _ in 0..20 {
+        let mid = (a + b) / 2.0;
+        if plane.distance(embedding.embed(inside * (1.0 - mid) + outside * mid)) >= 0.0 {
+            a = mid;
+        } else {
+            b = mid;
+        }
+    }
+    (a + b) / 2.0
+}
+
+impl CpuMesh {
+    /// Cuts away the triangles whose embedding is behind `plane`.
+    ///
+    /// Returns the remaining mesh, still in input space with its attributes interpolated, and the
+    /// cap closing every cut outline.
+    /// The cap lies on the plane and is in embedded space, so it can be refined there before
+    /// mapping it back with `invert`. Holes inside a cut outline are capped over.
+    pub fn clip(&self, embedding: &dyn Embedding<Vec3>, plane: &Plane) -> (Self, Self) {
+        let inside: Vec<bool> = self
+            .positions
+            .iter()
+            .map(|&pos| plane.distance(embedding.embed(pos)) >= 0.0)
+            .collect();
+
+        let mut mesh = self.clone();
+        let mut indices = Vec::new();
+        let mut crossings: HashMap<(u32, u32), u32> = HashMap::new();
+        // Cap outline segments, reversed relative to the kept triangles so the cap closes
+        // the surface with a matching winding
+        let mut outline = Outline::new();
+        let mut outline_points: HashMap<WeldKey, Vec3> = HashMap::new();
+
+        for triangle in self.indices.chunks(3) {
+            let kept = triangle.iter().filter(|&&i| inside[i as usize]).count();
+            match kept {
+                0 => continue,
+                3 => {
+                    indices.extend(triangle);
+                    continue;
+                }
+                _ => {}
+            }
+
+            let mut polygon = Vec::with_capacity(4);
+            let mut exit = None;
+            let mut enter = None;
+            for i in 0..3 {
+                let (p, q) = (triangle[i], triangle[(i + 1) % 3]);
+                if inside[p as usize] {
+                    polygon.push(p);
+                }
+                if inside[p as usize] != inside[q as usize] {
+                    let cut = *crossings.entry(edge_key(p, q)).or_insert_with(|| {
+                        let (a, b) = if inside[p as usize] { (p, q) } else { (q, p) };
+                        let t = crossing(
+                            embedding,
+                            plane,
+                            self.positions[a as usize],
+                            self.positions[b as usize],
+                        );
+                        mesh.extend_mixed(&[(a, b, t)]);
+                        mesh.positions.len() as u32 - 1
+                    });
+                    polygon.push(cut);
+                    if inside[p as usize] {
+                        exit = Some(cut);
+                    } else {
+                        enter = Some(cut);
+                    }
+                }
+            }
+            for i in 1..polygon.len() - 1 {
+                indices.extend(&[polygon[0], polygon[i], polygon[i + 1]]);
+            }
+
+            if let (Some(exit), Some(enter)) = (exit, enter) {
+                let from = embedding.embed(mesh.positions[enter as usize]);
+                let to = embedding.embed(mesh.positions[exit as usize]);
+                let (from_key, to_key) = (weld_key(from), weld_key(to));
+                if from_key != to_key {
+                    outline.entry(from_key).or_default().push((to_key, to));
+                    outline_points.insert(from_key, from);
+                }
+            }
+        }
+
+        let mut cap = CpuMesh::new(Vec::new(), Some(Vec::new()));
+        let (u, v) = plane.basis();
+        while let Some(&start) = outline.keys().next() {
+            let mut points = vec![outline_points[&start]];
+            let mut key = start;
+            while let Some((next, pos)) = take_segment(&mut outline, key) {
+                if next == start {
+                    break;
+                }
+                points.push(pos);
+                key = next;
+            }
+            if points.len() < 3 {
+                continue;
+            }
+            let flat: Vec<Vec2> = points.iter().map(|p| vec2(p.dot(u), p.dot(v))).collect();
+            let offset = cap.positions.len() as u32;
+            cap.positions.extend(&points);
+            cap.indices
+                .extend(ear_clip(&flat).into_iter().map(|i| i + offset));
+        }
+
+        mesh.indices = indices;
+        (mesh, cap)
+    }
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Triangulates a simple polygon, keeping its winding.
+fn ear_clip(points: &[Vec2]) -> Vec<u32> {
+    let area: f32 = (0..points.len())
+        .map(|i| cross(points[i], points[(i + 1) % points.len()]))
+        .sum();
+    let sign = area.signum();
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut indices = Vec::with_capacity((points.len() - 2) * 3);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (
+                points[remaining[(i + n - 1) % n]],
+                points[remaining[i]],
+                points[remaining[(i + 1) % n]],
+            );
+            if cross(b - a, c - b) * sign <= 0.0 {
+                return false;
+            }
+            remaining.iter().all(|&j| {
+                let p = points[j];
+                p == a
+                    || p == b
+                    || p == c
+                    || cross(b - a, p - a) * sign < 0.0
+                    || cross(c - b, p - b) * sign < 0.0
+                    || cross(a - c, p - c) * sign < 0.0
+            })
+        });
+        // Outlines that aren't simple polygons get a fan over what's left
+        let i = match ear {
+            Some(i) => i,
+            None => break,
+        };
+        indices.extend(&[
+            remaining[(i + n - 1) % n] as u32,
+            remaining[i] as u32,
+            remaining[(i + 1) % n] as u32,
+        ]);
+        remaining.remove(i);
+    }
+    for i in 1..remaining.len() - 1 {
+        indices.extend(&[
+            remaining[0] as u32,
+            remaining[i] as u32,
+            remaining[i + 1] as u32,
+        ]);
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use three_d::vec3;
+
+    use super::*;
+    use crate::{
+        embed::{CylindricalEmbedding, IdentityEmbedding},
+        pre_embed::{cube, revolution},
+    };
+
+    /// The clipped mesh closed by its cap, both in embedded space.
+    fn closed(mesh: &CpuMesh, embedding: &dyn Embedding<Vec3>, plane: &Plane) -> CpuMesh {
+        let (mut clipped, cap) = mesh.clip(embedding, plane);
+        clipped
+            .positions
+            .iter_mut()
+            .for_each(|pos| *pos = embedding.embed(*pos));
+        clipped.extend(&cap);
+        clipped.weld(WELD).remove_unused()
+    }
+
+    /// Area of `mesh` projected along `normal`, counting triangles facing away as negative.
+    fn facing_area(mesh: &CpuMesh, normal: Vec3) -> f32 {
+        mesh.indices
+            .chunks(3)
+            .map(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| mesh.positions[i as usize]);
+                (b - a).cross(c - a).dot(normal) / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn clipped_cube_is_closed() {
+        let mesh = cube(3, 3, 3);
+        let plane = Plane::new(vec3(1.0, 0.7, 0.4), 1.05);
+        let closed = closed(&mesh, &IdentityEmbedding {}, &plane);
+        assert!(closed.topology().is_watertight());
+        assert!(closed
+            .positions
+            .iter()
+            .all(|&pos| plane.distance(pos) >= -1e-4));
+    }
+
+    #[test]
+    fn clipped_revolution_is_closed() {
+        let mesh = revolution(12, 3, 1, |t| (t, 1.0 - t / 2.0));
+        // Keeps hues around 0.5, away from the seam at 0
+        let plane = Plane::new(vec3(-1.0, 0.0, 0.05), 0.2);
+        let closed = closed(&mesh, &CylindricalEmbedding {}, &plane);
+        assert!(closed.topology().is_watertight());
+    }
+
+    #[test]
+    fn caps_across_the_seam() {
+        // The cut runs along the seam twice, once for each of the faces at turn 0 and 1
+        let mesh = revolution(12, 3, 1, |t| (t, 1.0));
+        let plane = Plane::new(vec3(0.0, 1.0, 0.0), 0.5);
+        // Whichever seam face is cut first, the cap covers the whole section
+        let mut reversed = mesh.clone();
+        reversed.indices = mesh.indices.chunks(3).rev().flatten().copied().collect();
+        for mesh in [mesh, reversed] {
+            let (_, cap) = mesh.clip(&CylindricalEmbedding {}, &plane);
+            // The cut meets the unit circle at the 12 hues and between them on the diagonals. The
+            // cylindrical embedding mirrors, so the cap winds facing up to match the kept surface.
+            let area = 12.0 * (PI / 12.0).sin();
+            assert!((facing_area(&cap, Vec3::unit_y()) - area).abs() < 1e-3);
+            assert!(cap.positions.iter().all(|pos| (pos.y - 0.5).abs() < 1e-4));
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use three_d::Vec3;
+
+use super::CpuMesh;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    Obj,
+    /// Binary little endian PLY.
+    Ply,
+    /// Binary glTF 2.0 (`.glb`).
+    Gltf,
+}
+
+impl MeshFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "obj" => Some(Self::Obj),
+            "ply" => Some(Self::Ply),
+            "gltf" | "glb" => Some(Self::Gltf),
+            _ => None,
+        }
+    }
+}
+
+/// A mesh with one sRGB color in `0..1` per vertex.
+#[derive(Clone)]
+pub struct ColoredMesh {
+    pub mesh: CpuMesh,
+    pub colors: Vec<Vec3>,
+}
+
+impl ColoredMesh {
+    pub fn extend(&mut self, other: &Self) {
+        self.mesh.extend(&other.mesh);
+        self.colors.extend(&other.colors);
+    }
+
+    pub fn export(&self, format: MeshFormat) -> Vec<u8> {
+        match format {
+            MeshFormat::Obj => self.to_obj().into_bytes(),
+            MeshFormat::Ply => self.to_ply(),
+            MeshFormat::Gltf => self.to_glb(),
+        }
+    }
+
+    /// Wavefront OBJ, with colors in the common `v x y z r g b` extension.
+    pub fn to_obj(&self) -> String {
+        let mut obj = String::from("# burnt umber color solid\n");
+        for (pos, color) in self.mesh.positions.iter().zip(&self.colors) {
+            obj.push_str(&format!(
+                "v {} {} {} {} {} {}\n",
+                pos.x, pos.y, pos.z, color.x, color.y, color.z
+            ));
+        }
+        for triangle in self.mesh.indices.chunks(3) {
+            obj.push_str(&format!(
+                "f {} {} {}\n",
+                triangle[0] + 1,
+                triangle[1] + 1,
+                triangle[2] + 1
+            ));
+        }
+        obj
+    }
+
+    pub fn to_ply(&self) -> Vec<u8> {
+        let header = format!(
+            "ply\n\
+             format binary_little_endian 1.0\n\
+             element vertex {}\n\
+             property float x\n\
+             property float y\n\
+             property float z\n\
+             property uchar red\n\
+             property uchar green\n\
+             property uchar blue\n\
+             element face {}\n\
+             property list uchar uint vertex_indices\n\
+             end_header\n",
+            self.mesh.positions.len(),
+            self.mesh.indices.len() / 3
+        );
+        let mut ply = header.into_bytes();
+        for (pos, color) in self.mesh.positions.iter().zip(&self.colors) {
+            for v in [pos.x, pos.y, pos.z] {
+                ply.extend(v.to_le_bytes());
+            }
+            for c in [color.x, color.y, color.z] {
+                ply.push((c.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        for triangle in self.mesh.indices.chunks(3) {
+            ply.push(3);
+            for i in triangle {
+                ply.extend(i.to_le_bytes());
+            }
+        }
+        ply
+    }
+
+    /// Binary glTF with positions, linear `COLOR_0` and indices in one buffer.
+    pub fn to_glb(&self) -> Vec<u8> {
+        let mut bin = Vec::new();
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for pos in &self.mesh.positions {
+            for (i, v) in [pos.x, pos.y, pos.z].into_iter().enumerate() {
+                min[i] = min[i].min(v);
+                max[i] = max[i].max(v);
+                bin.extend(v.to_le_bytes());
+            }
+        }
+        let positions_len = bin.len();
+        for color in &self.colors {
+            for c in [color.x, color.y, color.z] {
+                bin.extend(srgb_to_linear(c.clamp(0.0, 1.0)).to_le_bytes());
+            }
+        }
+        let colors_len = bin.len() - positions_len;
+        for i in &self.mesh.indices {
+            bin.extend(i.to_le_bytes());
+        }
+        let indices_len = bin.len() - positions_len - colors_len;
+        let vertex_count = self.mesh.positions.len();
+        if vertex_count == 0 {
+            min = [0.0; 3];
+            max = [0.0; 3];
+        }
+
+        let json = format!(
+            r#"{{"asset":{{"version":"2.0","generator":"burnt-umber"}},"scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"COLOR_0":1}},"indices":2,"mode":4}}]}}],"buffers":[{{"byteLength":{}}}],"bufferViews":[{{"buffer":0,"byteOffset":0,"byteLength":{},"target":34962}},{{"buffer":0,"byteOffset":{},"byteLength":{},"target":34962}},{{"buffer":0,"byteOffset":{},"byteLength":{},"target":34963}}],"accessors":[{{"bufferView":0,"componentType":5126,"count":{},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}},{{"bufferView":1,"componentType":5126,"count":{},"type":"VEC3"}},{{"bufferView":2,"componentType":5125,"count":{},"type":"SCALAR"}}]}}"#,
+            bin.len(),
+            positions_len,
+            positions_len,
+            colors_len,
+            positions_len + colors_len,
+            indices_len,
+            vertex_count,
+            min[0],
+            min[1],
+            min[2],
+            max[0],
+            max[1],
+            max[2],
+            self.colors.len(),
+            self.mesh.indices.len(),
+        );
+
+        // Chunks are padded to 4 bytes, JSON with spaces and binary with zeros
+        let mut json = json.into_bytes();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        while bin.len() % 4 != 0 {
+            bin.push(0);
+        }
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::with_capacity(total);
+        glb.extend(b"glTF");
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((total as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(b"JSON");
+        glb.extend(json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(b"BIN\0");
+        glb.extend(bin);
+        glb
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::pre_embed::cube;
+
+    fn colored_cube() -> ColoredMesh {
+        let mesh = cube(2, 2, 2);
+        let colors = mesh.positions.clone();
+        ColoredMesh { mesh, colors }
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn obj_round_trips() {
+        let colored = colored_cube();
+        let obj = colored.export(MeshFormat::Obj);
+        let mesh = CpuMesh::import(&obj, MeshFormat::Obj).unwrap();
+        assert_eq!(mesh.positions, colored.mesh.positions);
+        assert_eq!(mesh.indices, colored.mesh.indices);
+        assert!(mesh.topology().is_watertight());
+    }
+
+    #[test]
+    fn ply_round_trips() {
+        let colored = colored_cube();
+        let ply = colored.export(MeshFormat::Ply);
+        let mesh = CpuMesh::import(&ply, MeshFormat::Ply).unwrap();
+        assert_eq!(mesh.positions, colored.mesh.positions);
+        assert_eq!(mesh.indices, colored.mesh.indices);
+        assert!(mesh.topology().is_watertight());
+    }
+
+    #[test]
+    fn welded_soup_exports_closed() {
+        // What the color space draws, and welds before exporting
+        let mesh = cube(2, 2, 2).split_triangles().weld(1e-6);
+        let colored = ColoredMesh {
+            colors: mesh.positions.clone(),
+            mesh,
+        };
+        let obj = colored.export(MeshFormat::Obj);
+        let mesh = CpuMesh::import(&obj, MeshFormat::Obj).unwrap();
+        assert!(mesh.topology().is_watertight());
+    }
+
+    #[test]
+    fn lays_out_glb_chunks() {
+        let colored = colored_cube();
+        let glb = colored.export(MeshFormat::Gltf);
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32_at(&glb, 4), 2);
+        assert_eq!(u32_at(&glb, 8) as usize, glb.len());
+
+        let json_len = u32_at(&glb, 12) as usize;
+        assert_eq!(&glb[16..20], b"JSON");
+        assert_eq!(json_len % 4, 0);
+        let json: Value = serde_json::from_slice(&glb[20..20 + json_len]).unwrap();
+        let bin_start = 20 + json_len;
+        let bin_len = u32_at(&glb, bin_start) as usize;
+        assert_eq!(&glb[bin_start + 4..bin_start + 8], b"BIN\0");
+        assert_eq!(bin_len % 4, 0);
+        let bin = &glb[bin_start + 8..];
+        assert_eq!(bin.len(), bin_len);
+        assert!(json["buffers"][0]["byteLength"].as_u64().unwrap() as usize <= bin_len);
+
+        let vertices = colored.mesh.positions.len();
+        let accessors = json["accessors"].as_array().unwrap();
+        assert_eq!(accessors[0]["count"], vertices);
+        assert_eq!(accessors[1]["count"], vertices);
+        assert_eq!(accessors[2]["count"], colored.mesh.indices.len());
+        for (bound, value) in [("min", 0.0), ("max", 1.0)] {
+            let bound: Vec<f64> = accessors[0][bound]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(Value::as_f64)
+                .collect();
+            assert_eq!(bound, [value; 3]);
+        }
+
+        // Views follow each other without gaps
+        let views = json["bufferViews"].as_array().unwrap();
+        let lengths = [vertices * 12, vertices * 12, colored.mesh.indices.len() * 4];
+        let mut offset = 0;
+        for (view, length) in views.iter().zip(lengths) {
+            assert_eq!(view["byteOffset"], offset);
+            assert_eq!(view["byteLength"], length);
+            offset += length;
+        }
+
+        let indices = views[2]["byteOffset"].as_u64().unwrap() as usize;
+        for (i, &index) in colored.mesh.indices.iter().enumerate() {
+            assert_eq!(u32_at(bin, indices + i * 4), index);
+        }
+        let x = f32::from_le_bytes(bin[0..4].try_into().unwrap());
+        assert_eq!(x, colored.mesh.positions[0].x);
+    }
+}
@@ -21,7 +21,7 @@ pub fn _cube() -> CpuMesh {
         1, 5, 3, 3, 5, 7, // Front (z = 0)
     ];
 
-    let mut cube = CpuMesh { positions, indices };
+    let mut cube = CpuMesh::new(positions, Some(indices));
     cube.face_away(vec3(0.5, 0.5, 0.5));
     cube
 }
@@ -0,0 +1,483 @@
+use derive_more::Display;
+use three_d::{vec3, Vec3};
+
+use super::{export::MeshFormat, CpuMesh};
+
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum ImportError {
+    #[display(fmt = "line {}: {}", _0, _1)]
+    Parse(usize, String),
+    #[display(fmt = "unsupported: {}", _0)]
+    Unsupported(String),
+    #[display(fmt = "unexpected end of file")]
+    Truncated,
+    #[display(fmt = "vertex index {} is out of range", _0)]
+    IndexOutOfRange(i64),
+}
+
+impl CpuMesh {
+    /// Reads a mesh whose positions are in input space, e.g. a sampling shape around a color.
+    pub fn import(bytes: &[u8], format: MeshFormat) -> Result<Self, ImportError> {
+        match format {
+            MeshFormat::Obj => {
+                let src = std::str::from_utf8(bytes)
+                    .map_err(|error| ImportError::Parse(0, error.to_string()))?;
+                Self::from_obj(src)
+            }
+            MeshFormat::Ply => Self::from_ply(bytes),
+            MeshFormat::Gltf => Err(ImportError::Unsupported("glTF import".to_string())),
+        }
+    }
+
+    /// Reads `v` and `f` statements, fanning polygons into triangles. Everything else is ignored.
+    pub fn from_obj(src: &str) -> Result<Self, ImportError> {
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for (number, line) in src.lines().enumerate() {
+            let number = number + 1;
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let coords = words
+                        .take(3)
+                        .map(|word| word.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|error| ImportError::Parse(number, error.to_string()))?;
+                    if coords.len() != 3 {
+                        return Err(ImportError::Parse(number, "expected x y z".to_string()));
+                    }
+                    positions.push(vec3(coords[0], coords[1], coords[2]));
+                }
+                Some("f") => {
+                    let face = words
+                        .map(|word| {
+                            // Only the position of `v/vt/vn` matters
+                            let index = word.split('/').next().unwrap_or(word);
+                            let index: i64 = index
+                                .parse()
+                                .map_err(|_| ImportError::Parse(number, word.to_string()))?;
+                            // Negative indices count back from the latest vertex
+                            let resolved = if index < 0 {
+                                positions.len() as i64 + index
+                            } else {
+                                index - 1
+                            };
+                            if resolved < 0 || resolved >= positions.len() as i64 {
+                                return Err(ImportError::IndexOutOfRange(index));
+                            }
+                            Ok(resolved as u32)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if face.len() < 3 {
+                        return Err(ImportError::Parse(number, "face needs 3 vertices".into()));
+                    }
+                    for i in 1..face.len() - 1 {
+                        indices.extend(&[face[0], face[i], face[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Self::new(positions, Some(indices)))
+    }
+
+    /// Reads ASCII and binary PLY files with `x`, `y`, `z` vertex properties and a face list.
+    pub fn from_ply(bytes: &[u8]) -> Result<Self, ImportError> {
+        let (header, body) = ply_header(bytes)?;
+        let mut reader = match header.encoding {
+            Encoding::Ascii => {
+                let body = std::str::from_utf8(body)
+                    .map_err(|error| ImportError::Parse(0, error.to_string()))?;
+                let words = body.split_whitespace();
+                PlyReader::Ascii(words.clone(), words.count())
+            }
+            Encoding::LittleEndian => PlyReader::Binary(body, false),
+            Encoding::BigEndian => PlyReader::Binary(body, true),
+        };
+
+        // Counts come from the file, so they are checked against what is left before looping
+        let mut remaining = reader.remaining(Scalar::U8);
+        for element in &header.elements {
+            let size = element.min_size(header.encoding).max(1);
+            if element.count > remaining / size {
+                return Err(ImportError::Truncated);
+            }
+            remaining -= element.count * size;
+        }
+
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for element in &header.elements {
+            for _ in 0..element.count {
+                let mut pos = Vec3::new(0.0, 0.0, 0.0);
+                for property in &element.properties {
+                    match property {
+                        Property::Scalar(name, ty) => {
+                            let value = reader.read(*ty)?;
+                            match (element.name.as_str(), name.as_str()) {
+                                ("vertex", "x") => pos.x = value as f32,
+                                ("vertex", "y") => pos.y = value as f32,
+                                ("vertex", "z") => pos.z = value as f32,
+                                _ => {}
+                            }
+                        }
+                        Property::List(name, count_ty, item_ty) => {
+                            let count = reader.read(*count_ty)? as usize;
+                            if count > reader.remaining(*item_ty) {
+                                return Err(ImportError::Truncated);
+                            }
+                            let mut face = Vec::with_capacity(count);
+                            for _ in 0..count {
+                                face.push(reader.read(*item_ty)? as i64);
+                            }
+                            if element.name == "face"
+                                && (name == "vertex_indices" || name == "vertex_index")
+                            {
+                                for i in 1..face.len().saturating_sub(1) {
+                                    indices.extend(&[face[0], face[i], face[i + 1]]);
+                                }
+                            }
+                        }
+                    }
+                }
+                if element.name == "vertex" {
+                    positions.push(pos);
+                }
+            }
+        }
+
+        let indices = indices
+            .into_iter()
+            .map(|i| {
+                if i < 0 || i >= positions.len() as i64 {
+                    Err(ImportError::IndexOutOfRange(i))
+                } else {
+                    Ok(i as u32)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(positions, Some(indices)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    /// Bytes, or words in ASCII files, taken by one element whose lists are all empty.
+    fn min_size(&self, encoding: Encoding) -> usize {
+        self.properties
+            .iter()
+            .map(|property| match (encoding, property) {
+                (Encoding::Ascii, _) => 1,
+                (_, Property::Scalar(_, ty) | Property::List(_, ty, _)) => ty.size(),
+            })
+            .sum()
+    }
+}
+
+struct PlyHeader {
+    encoding: Encoding,
+    elements: Vec<Element>,
+}
+
+fn ply_header(bytes: &[u8]) -> Result<(PlyHeader, &[u8]), ImportError> {
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|window| window == END)
+        .ok_or(ImportError::Truncated)?;
+    let body_start = bytes[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|i| end + i + 1)
+        .unwrap_or(bytes.len());
+    let header = std::str::from_utf8(&bytes[..end])
+        .map_err(|error| ImportError::Parse(0, error.to_string()))?;
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for (number, line) in header.lines().enumerate() {
+        let number = number + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let scalar = |name: &str| {
+            Scalar::from_name(name)
+                .ok_or_else(|| ImportError::Parse(number, format!("unknown type {}", name)))
+        };
+        match words.as_slice() {
+            ["ply"] | [] => {}
+            ["comment", ..] | ["obj_info", ..] => {}
+            ["format", format, _] => {
+                encoding = Some(match *format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::LittleEndian,
+                    "binary_big_endian" => Encoding::BigEndian,
+                    _ => return Err(ImportError::Unsupported(format.to_string())),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| ImportError::Parse(number, line.to_string()))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, item_ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| ImportError::Parse(number, line.to_string()))?
+                .properties
+                .push(Property::List(
+                    name.to_string(),
+                    scalar(count_ty)?,
+                    scalar(item_ty)?,
+                )),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| ImportError::Parse(number, line.to_string()))?
+                .properties
+                .push(Property::Scalar(name.to_string(), scalar(ty)?)),
+            _ => return Err(ImportError::Parse(number, line.to_string())),
+        }
+    }
+    let encoding = encoding.ok_or_else(|| ImportError::Parse(0, "missing format".into()))?;
+    Ok((PlyHeader { encoding, elements }, &bytes[body_start..]))
+}
+
+enum PlyReader<'a> {
+    /// Remaining words, and how many there are.
+    Ascii(std::str::SplitWhitespace<'a>, usize),
+    /// Remaining bytes, and whether they are big endian.
+    Binary(&'a [u8], bool),
+}
+
+impl<'a> PlyReader<'a> {
+    /// How many values of type `ty` could still be read.
+    fn remaining(&self, ty: Scalar) -> usize {
+        match self {
+            Self::Ascii(_, count) => *count,
+            Self::Binary(bytes, _) => bytes.len() / ty.size(),
+        }
+    }
+
+    fn read(&mut self, ty: Scalar) -> Result<f64, ImportError> {
+        match self {
+            Self::Ascii(words, count) => {
+                let word = words.next().ok_or(ImportError::Truncated)?;
+                *count -= 1;
+                word.parse()
+                    .map_err(|_| ImportError::Parse(0, format!("bad number {}", word)))
+            }
+            Self::Binary(bytes, big) => {
+                let size = ty.size();
+                if bytes.len() < size {
+                    return Err(ImportError::Truncated);
+                }
+                let mut raw = [0u8; 8];
+                raw[..size].copy_from_slice(&bytes[..size]);
+                if *big {
+                    raw[..size].reverse();
+                }
+                *bytes = &bytes[size..];
+                Ok(match ty {
+                    Scalar::I8 => raw[0] as i8 as f64,
+                    Scalar::U8 => raw[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    Scalar::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    Scalar::F32 => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    Scalar::F64 => f64::from_le_bytes(raw),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII_HEADER: &str = "ply\n\
+        format ascii 1.0\n\
+        comment a unit triangle\n\
+        element vertex 3\n\
+        property float x\n\
+        property float y\n\
+        property float z\n\
+        element face 1\n\
+        property list uchar int vertex_indices\n\
+        end_header\n";
+
+    fn triangle() -> Vec<Vec3> {
+        vec![
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ]
+    }
+
+    /// A binary PLY with the vertices of `triangle` and one face list starting with `count`.
+    fn binary_ply(big_endian: bool, count: u32, indices: &[u32]) -> Vec<u8> {
+        let format = if big_endian { "big" } else { "little" };
+        let mut ply = format!(
+            "ply\nformat binary_{}_endian 1.0\nelement vertex 3\nproperty float x\n\
+             property float y\nproperty float z\nelement face 1\n\
+             property list uint uint vertex_indices\nend_header\n",
+            format
+        )
+        .into_bytes();
+        let bytes = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        for pos in triangle() {
+            for v in [pos.x, pos.y, pos.z] {
+                ply.extend(bytes(v.to_bits()));
+            }
+        }
+        ply.extend(bytes(count));
+        for &i in indices {
+            ply.extend(bytes(i));
+        }
+        ply
+    }
+
+    #[test]
+    fn reads_obj() {
+        let obj = "# square\n\
+            v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+            vt 0 0\n\
+            f 1/1 2/1 3/1 4/1\n\
+            f -4 -2 -1\n";
+        let mesh = CpuMesh::from_obj(obj).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3, 0, 2, 3]);
+    }
+
+    #[test]
+    fn rejects_bad_obj() {
+        assert_eq!(
+            CpuMesh::from_obj("v 0 0 0\nf 1 2 3\n").err(),
+            Some(ImportError::IndexOutOfRange(2))
+        );
+        assert!(matches!(
+            CpuMesh::from_obj("v 0 0\n"),
+            Err(ImportError::Parse(1, _))
+        ));
+        assert!(matches!(
+            CpuMesh::from_obj("v 0 0 0\nv 1 0 0\nf 1 2\n"),
+            Err(ImportError::Parse(3, _))
+        ));
+    }
+
+    #[test]
+    fn reads_ascii_ply() {
+        let ply = format!("{}0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n", ASCII_HEADER);
+        let mesh = CpuMesh::from_ply(ply.as_bytes()).unwrap();
+        assert_eq!(mesh.positions, triangle());
+        assert_eq!(mesh.indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn reads_binary_ply() {
+        for big_endian in [false, true] {
+            let mesh = CpuMesh::from_ply(&binary_ply(big_endian, 3, &[0, 1, 2])).unwrap();
+            assert_eq!(mesh.positions, triangle());
+            assert_eq!(mesh.indices, [0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_counts() {
+        // A list longer than the file must fail instead of allocating for it
+        let ply = binary_ply(false, u32::MAX, &[0, 1, 2]);
+        assert_eq!(CpuMesh::from_ply(&ply).err(), Some(ImportError::Truncated));
+        let ply = format!("{}0 0 0\n1 0 0\n0 1 0\n1e18 0 1 2\n", ASCII_HEADER);
+        assert_eq!(
+            CpuMesh::from_ply(ply.as_bytes()).err(),
+            Some(ImportError::Truncated)
+        );
+
+        let header = ASCII_HEADER.replace("vertex 3", "vertex 1000000000000");
+        let ply = format!("{}0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n", header);
+        assert_eq!(
+            CpuMesh::from_ply(ply.as_bytes()).err(),
+            Some(ImportError::Truncated)
+        );
+        let ply = binary_ply(false, 3, &[0, 1]);
+        assert_eq!(CpuMesh::from_ply(&ply).err(), Some(ImportError::Truncated));
+    }
+
+    #[test]
+    fn rejects_bad_ply() {
+        assert_eq!(
+            CpuMesh::from_ply(b"ply\nformat ascii 1.0\n").err(),
+            Some(ImportError::Truncated)
+        );
+        let ply = binary_ply(false, 3, &[0, 1, 3]);
+        assert_eq!(
+            CpuMesh::from_ply(&ply).err(),
+            Some(ImportError::IndexOutOfRange(3))
+        );
+        let header = ASCII_HEADER.replace("float x", "quad x");
+        assert!(matches!(
+            CpuMesh::from_ply(header.as_bytes()),
+            Err(ImportError::Parse(5, _))
+        ));
+    }
+}
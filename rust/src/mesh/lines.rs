@@ -0,0 +1,128 @@
+use three_d::{vec2, Vec2};
+
+use super::{
+    attribute::{Attribute, AttributeData},
+    CpuMesh,
+};
+
+impl CpuMesh {
+    /// A line between the vertices of every pair of indices, for `DrawMode::Lines`.
+    pub fn line_list(&self) -> Self {
+        let segments = self
+            .indices
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect();
+        self.line_quads(segments)
+    }
+
+    /// A line through the vertices of the indices, in order, for `DrawMode::Lines`.
+    // Kept for elements drawing curves, though none does yet
+    #[allow(dead_code)]
+    pub fn line_strip(&self) -> Self {
+        let segments = self
+            .indices
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect();
+        self.line_quads(segments)
+    }
+
+    /// A point at every vertex, for `DrawMode::Points`.
+    pub fn points(&self) -> Self {
+        let corners = [
+            vec2(-1.0, -1.0),
+            vec2(1.0, -1.0),
+            vec2(1.0, 1.0),
+            vec2(-1.0, 1.0),
+        ];
+        let count = self.positions.len() as u32;
+        let gathered: Vec<u32> = (0..count).flat_map(|i| [i; 4]).collect();
+        let indices = (0..count)
+            .flat_map(|i| [0, 1, 2, 0, 2, 3].map(|corner| i * 4 + corner))
+            .collect();
+        let corners: Vec<Vec2> = (0..count).flat_map(|_| corners).collect();
+        self.gather(&gathered, indices)
+            .with_attribute(Attribute::Corner, AttributeData::Vec2(corners))
+    }
+
+    /// A quad along every segment, with its vertices at the segment ends. The shader moves
+    /// them apart across the segment on screen.
+    fn line_quads(&self, segments: Vec<(u32, u32)>) -> Self {
+        let mut gathered = Vec::with_capacity(segments.len() * 4);
+        let mut ends = Vec::with_capacity(segments.len() * 4);
+        let mut corners = Vec::with_capacity(segments.len() * 4);
+        let mut indices = Vec::with_capacity(segments.len() * 6);
+        for (a, b) in segments {
+            let offset = gathered.len() as u32;
+            let (pos_a, pos_b) = (self.positions[a as usize], self.positions[b as usize]);
+            gathered.extend([a, a, b, b]);
+            ends.extend([pos_b, pos_b, pos_a, pos_a]);
+            // Seen from `b` the segment runs the other way, so its sides swap
+            corners.extend([
+                vec2(-1.0, 0.0),
+                vec2(1.0, 0.0),
+                vec2(-1.0, 1.0),
+                vec2(1.0, 1.0),
+            ]);
+            indices.extend([0, 1, 2, 0, 2, 3].map(|i| i + offset));
+        }
+        self.gather(&gathered, indices)
+            .with_attribute(Attribute::LineEnd, AttributeData::Vec3(ends))
+            .with_attribute(Attribute::Corner, AttributeData::Vec2(corners))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use three_d::{vec3, Vec3};
+
+    use super::*;
+
+    fn path() -> CpuMesh {
+        let positions = vec![
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 1.0),
+        ];
+        CpuMesh::new(positions, Some(vec![0, 1, 2, 3]))
+    }
+
+    /// The segments of line quads, read back from their corners and `LineEnd`s.
+    fn segments(lines: &CpuMesh) -> Vec<(Vec3, Vec3)> {
+        let ends = match lines.attribute(Attribute::LineEnd) {
+            Some(AttributeData::Vec3(ends)) => ends,
+            _ => panic!("lines carry their ends"),
+        };
+        (0..lines.positions.len())
+            .step_by(4)
+            .map(|i| (lines.positions[i], ends[i]))
+            .collect()
+    }
+
+    #[test]
+    fn strips_join_consecutive_vertices() {
+        let mesh = path();
+        let strip = mesh.line_strip();
+        let p = &mesh.positions;
+        assert_eq!(segments(&strip), [(p[0], p[1]), (p[1], p[2]), (p[2], p[3])]);
+        assert_eq!(strip.indices.len(), 3 * 6);
+        assert!(strip.attribute(Attribute::Corner).is_some());
+    }
+
+    #[test]
+    fn lists_join_pairs() {
+        let mesh = path();
+        let p = &mesh.positions;
+        assert_eq!(segments(&mesh.line_list()), [(p[0], p[1]), (p[2], p[3])]);
+    }
+
+    #[test]
+    fn points_are_quads() {
+        let points = path().points();
+        assert_eq!(points.positions.len(), 4 * 4);
+        assert_eq!(points.indices.len(), 4 * 6);
+        assert_eq!(points.positions[4..8], [vec3(1.0, 0.0, 0.0); 4]);
+    }
+}
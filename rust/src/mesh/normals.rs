@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use cgmath::InnerSpace;
+use three_d::Vec3;
+
+use super::CpuMesh;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normals {
+    /// Every corner gets the normal of its triangle.
+    Face,
+    /// Area weighted average over the triangles meeting at a position, so split triangles
+    /// still shade smoothly.
+    Smooth,
+}
+
+/// Positions closer than this share a smooth normal.
+const WELD: f32 = 1e-5;
+
+impl CpuMesh {
+    /// Unit normal of every triangle, zero for degenerate ones.
+    pub fn face_normals(&self) -> Vec<Vec3> {
+        self.indices
+            .chunks(3)
+            .map(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| self.positions[i as usize]);
+                let normal = (b - a).cross(c - a);
+                if normal.magnitude2() > 0.0 {
+                    normal.normalize()
+                } else {
+                    normal
+                }
+            })
+            .collect()
+    }
+
+    /// One normal per position.
+    pub fn normals(&self, normals: Normals) -> Vec<Vec3> {
+        let zero = Vec3::new(0.0, 0.0, 0.0);
+        let mut result = vec![zero; self.positions.len()];
+        match normals {
+            Normals::Face => {
+                for (t, normal) in self.indices.chunks(3).zip(self.face_normals()) {
+                    for &i in t {
+                        result[i as usize] = normal;
+                    }
+                }
+            }
+            Normals::Smooth => {
+                let key = |pos: Vec3| {
+                    (
+                        (pos.x / WELD).round() as i32,
+                        (pos.y / WELD).round() as i32,
+                        (pos.z / WELD).round() as i32,
+                    )
+                };
+                let mut sums: HashMap<(i32, i32, i32), Vec3> = HashMap::new();
+                for t in self.indices.chunks(3) {
+                    let [a, b, c] = [t[0], t[1], t[2]].map(|i| self.positions[i as usize]);
+                    // The cross product's length is twice the area, weighting big triangles more
+                    let normal = (b - a).cross(c - a);
+                    for pos in [a, b, c] {
+                        *sums.entry(key(pos)).or_insert(zero) += normal;
+                    }
+                }
+                for (normal, &pos) in result.iter_mut().zip(&self.positions) {
+                    let sum = sums.get(&key(pos)).copied().unwrap_or(zero);
+                    if sum.magnitude2() > 0.0 {
+                        *normal = sum.normalize();
+                    }
+                }
+            }
+        }
+        result
+    }
+}
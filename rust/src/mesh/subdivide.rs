@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+
+use cgmath::InnerSpace;
+use three_d::Vec3;
+
+use crate::embed::Embedding;
+
+use super::CpuMesh;
+
+/// How `subdivide` places vertices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subdivision {
+    /// New vertices at edge midpoints, old vertices stay put.
+    Linear,
+    /// Loop subdivision, smoothing the surface. Boundaries are smoothed as curves.
+    Loop,
+}
+
+impl Subdivision {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Self::Linear),
+            "loop" => Some(Self::Loop),
+            _ => None,
+        }
+    }
+}
+
+/// How far `adaptive_subdivide` may go.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Refinement {
+    /// Largest allowed distance between an embedded edge midpoint and the midpoint of the
+    /// embedded edge ends.
+    pub tolerance: f32,
+    pub max_triangles: usize,
+    pub max_passes: u32,
+}
+
+impl Default for Refinement {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.004,
+            max_triangles: 30_000,
+            max_passes: 6,
+        }
+    }
+}
+
+pub(super) fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// How far the embedded midpoint of `a` and `b` is from the straight line between them.
+fn edge_error(embeddings: &[&dyn Embedding<Vec3>], a: Vec3, b: Vec3) -> f32 {
+    let mid = (a + b) / 2.0;
+    embeddings
+        .iter()
+        .map(|embedding| {
+            let straight = (embedding.embed(a) + embedding.embed(b)) / 2.0;
+            (embedding.embed(mid) - straight).magnitude()
+        })
+        .fold(0.0, f32::max)
+}
+
+struct Edge {
+    midpoint: u32,
+    /// The corners facing the edge in the triangles using it.
+    opposite: Vec<u32>,
+}
+
+impl CpuMesh {
+    /// Splits every triangle into four, sharing the new edge vertices between neighbours.
+    pub fn subdivide(&self, scheme: Subdivision) -> Self {
+        let mut edges: HashMap<(u32, u32), Edge> = HashMap::new();
+        let mut next = self.positions.len() as u32;
+        for triangle in self.indices.chunks(3) {
+            for i in 0..3 {
+                let edge = edges
+                    .entry(edge_key(triangle[i], triangle[(i + 1) % 3]))
+                    .or_insert_with(|| {
+                        next += 1;
+                        Edge {
+                            midpoint: next - 1,
+                            opposite: Vec::new(),
+                        }
+                    });
+                edge.opposite.push(triangle[(i + 2) % 3]);
+            }
+        }
+
+        let mut positions = self.positions.clone();
+        positions.resize(next as usize, Vec3::new(0.0, 0.0, 0.0));
+        for (&(a, b), edge) in &edges {
+            let pa = self.positions[a as usize];
+            let pb = self.positions[b as usize];
+            positions[edge.midpoint as usize] = match (scheme, edge.opposite.as_slice()) {
+                (Subdivision::Loop, &[c, d]) => {
+                    let pc = self.positions[c as usize];
+                    let pd = self.positions[d as usize];
+                    (pa + pb) * 0.375 + (pc + pd) * 0.125
+                }
+                _ => (pa + pb) / 2.0,
+            };
+        }
+
+        if scheme == Subdivision::Loop {
+            let mut neighbours: Vec<Vec<u32>> = vec![Vec::new(); self.positions.len()];
+            let mut boundary: Vec<Vec<u32>> = vec![Vec::new(); self.positions.len()];
+            for (&(a, b), edge) in &edges {
+                neighbours[a as usize].push(b);
+                neighbours[b as usize].push(a);
+                if edge.opposite.len() != 2 {
+                    boundary[a as usize].push(b);
+                    boundary[b as usize].push(a);
+                }
+            }
+            for (i, pos) in self.positions.iter().enumerate() {
+                positions[i] = match (boundary[i].as_slice(), neighbours[i].len()) {
+                    (&[], 0) => *pos,
+                    (&[], n) => {
+                        let beta = if n == 3 {
+                            3.0 / 16.0
+                        } else {
+                            3.0 / (8.0 * n as f32)
+                        };
+                        let sum = neighbours[i]
+                            .iter()
+                            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &j| {
+                                sum + self.positions[j as usize]
+                            });
+                        *pos * (1.0 - n as f32 * beta) + sum * beta
+                    }
+                    (&[a, b], _) => {
+                        *pos * 0.75
+                            + (self.positions[a as usize] + self.positions[b as usize]) * 0.125
+                    }
+                    // Corners where the boundary isn't a simple curve stay put
+                    _ => *pos,
+                };
+            }
+        }
+
+        // Attributes are interpolated linearly whatever the scheme
+        let mut mixes = vec![(0, 0, 0.5); edges.len()];
+        for (&(a, b), edge) in &edges {
+            mixes[edge.midpoint as usize - self.positions.len()] = (a, b, 0.5);
+        }
+        let midpoints = edges
+            .iter()
+            .map(|(&key, edge)| (key, edge.midpoint))
+            .collect();
+        let mut mesh = Self {
+            positions,
+            indices: split_marked(&self.indices, &midpoints),
+            attributes: self.attributes.clone(),
+        };
+        mesh.extend_attributes_mixed(&mixes);
+        mesh
+    }
+
+    /// Subdivides `n` times, multiplying the triangle count by `4^n`.
+    pub fn subdivide_n(&self, scheme: Subdivision, n: u32) -> Self {
+        let mut mesh = self.clone();
+        for _ in 0..n {
+            mesh = mesh.subdivide(scheme);
+        }
+        mesh
+    }
+
+    /// Splits triangles whose edges bend too much under any of `embeddings`.
+    ///
+    /// Edges are chosen before triangles are split, so a split edge is split in every triangle
+    /// using it and no cracks open up. The worst edges are split first until the budget is spent.
+    pub fn adaptive_subdivide(
+        &self,
+        embeddings: &[&dyn Embedding<Vec3>],
+        refinement: &Refinement,
+    ) -> Self {
+        let mut mesh = self.clone();
+        for _ in 0..refinement.max_passes {
+            let mut edges: HashMap<(u32, u32), u32> = HashMap::new();
+            for triangle in mesh.indices.chunks(3) {
+                for i in 0..3 {
+                    *edges
+                        .entry(edge_key(triangle[i], triangle[(i + 1) % 3]))
+                        .or_insert(0) += 1;
+                }
+            }
+            let mut candidates: Vec<((u32, u32), u32, f32)> = edges
+                .into_iter()
+                .filter_map(|(edge, uses)| {
+                    let a = mesh.positions[edge.0 as usize];
+                    let b = mesh.positions[edge.1 as usize];
+                    let error = edge_error(embeddings, a, b);
+                    (error > refinement.tolerance).then_some((edge, uses, error))
+                })
+                .collect();
+            candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+            let mut triangles = mesh.indices.len() / 3;
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut mixes = Vec::new();
+            for (edge, uses, _) in candidates {
+                // Every triangle using the edge gains one more triangle
+                if triangles + uses as usize > refinement.max_triangles {
+                    break;
+                }
+                triangles += uses as usize;
+                midpoints.insert(edge, (mesh.positions.len() + mixes.len()) as u32);
+                mixes.push((edge.0, edge.1, 0.5));
+            }
+            mesh.extend_mixed(&mixes);
+            if midpoints.is_empty() {
+                break;
+            }
+            mesh.indices = split_marked(&mesh.indices, &midpoints);
+        }
+        mesh
+    }
+}
+
+/// Splits every triangle along the edges that have a midpoint, keeping the winding.
+pub(super) fn split_marked(indices: &[u32], midpoints: &HashMap<(u32, u32), u32>) -> Vec<u32> {
+    let mut new_indices = Vec::with_capacity(indices.len() * 2);
+    for triangle in indices.chunks(3) {
+        let corners = [triangle[0], triangle[1], triangle[2]];
+        let mids: Vec<Option<u32>> = (0..3)
+            .map(|i| {
+                midpoints
+                    .get(&edge_key(corners[i], corners[(i + 1) % 3]))
+                    .copied()
+            })
+            .collect();
+        let marked = mids.iter().filter(|mid| mid.is_some()).count();
+        match marked {
+            0 => new_indices.extend(&corners),
+            1 => {
+                // Rotate so the split edge goes from a to b
+                let i = mids.iter().position(|mid| mid.is_some()).unwrap();
+                let (a, b, c) = (corners[i], corners[(i + 1) % 3], corners[(i + 2) % 3]);
+                let ab = mids[i].unwrap();
+                new_indices.extend(&[a, ab, c, ab, b, c]);
+            }
+            2 => {
+                // Rotate so the edge that stays whole goes from c to a
+                let i = (mids.iter().position(|mid| mid.is_none()).unwrap() + 1) % 3;
+                let (a, b, c) = (corners[i], corners[(i + 1) % 3], corners[(i + 2) % 3]);
+                let ab = mids[i].unwrap();
+                let bc = mids[(i + 1) % 3].unwrap();
+                new_indices.extend(&[ab, b, bc, a, ab, bc, a, bc, c]);
+            }
+            _ => {
+                let (a, b, c) = (corners[0], corners[1], corners[2]);
+                let (ab, bc, ca) = (mids[0].unwrap(), mids[1].unwrap(), mids[2].unwrap());
+                new_indices.extend(&[a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+            }
+        }
+    }
+    new_indices
+}
+
+#[cfg(test)]
+mod tests {
+    use three_d::vec3;
+
+    use super::*;
+    use crate::pre_embed::plane;
+
+    fn tetrahedron() -> CpuMesh {
+        CpuMesh::new(
+            vec![
+                vec3(0.0, 0.0, 0.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+                vec3(0.0, 0.0, 1.0),
+            ],
+            Some(vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3]),
+        )
+    }
+
+    #[test]
+    fn shares_edge_midpoints() {
+        for scheme in [Subdivision::Linear, Subdivision::Loop] {
+            // Every edge gains one vertex, every triangle becomes four
+            let once = tetrahedron().subdivide(scheme);
+            assert_eq!(once.positions.len(), 4 + 6);
+            assert_eq!(once.indices.len() / 3, 16);
+            let twice = tetrahedron().subdivide_n(scheme, 2);
+            assert_eq!(twice.positions.len(), 10 + 24);
+            assert_eq!(twice.indices.len() / 3, 64);
+        }
+    }
+
+    #[test]
+    fn stays_watertight() {
+        for scheme in [Subdivision::Linear, Subdivision::Loop] {
+            let mesh = tetrahedron().subdivide_n(scheme, 3);
+            assert!(mesh.topology().is_watertight(), "{:?}", scheme);
+        }
+    }
+
+    #[test]
+    fn splits_boundaries() {
+        let grid = plane(2, 2, Vec3::unit_x(), Vec3::unit_y(), vec3(0.0, 0.0, 0.0));
+        let before = grid.topology();
+        for scheme in [Subdivision::Linear, Subdivision::Loop] {
+            let after = grid.subdivide(scheme).topology();
+            assert_eq!(after.vertices, 5 * 5);
+            assert_eq!(after.boundary_edges, before.boundary_edges * 2);
+            assert!(after.is_manifold());
+        }
+    }
+
+    #[test]
+    fn linear_keeps_vertices_in_place() {
+        let mesh = tetrahedron().subdivide(Subdivision::Linear);
+        assert_eq!(mesh.positions[..4], tetrahedron().positions[..]);
+        let loop_mesh = tetrahedron().subdivide(Subdivision::Loop);
+        assert_ne!(loop_mesh.positions[0], vec3(0.0, 0.0, 0.0));
+    }
+}
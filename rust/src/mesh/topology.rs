@@ -0,0 +1,299 @@
+use std::collections::{HashMap, HashSet};
+
+use cgmath::InnerSpace;
+use three_d::Vec3;
+
+use super::{subdivide::edge_key, CpuMesh};
+
+/// How the triangles of a mesh fit together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Topology {
+    pub vertices: usize,
+    pub triangles: usize,
+    /// Edges used by a single triangle.
+    pub boundary_edges: usize,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: usize,
+    /// Edges whose two triangles run along them in the same direction, i.e. flipped windings.
+    pub inconsistent_edges: usize,
+}
+
+impl Topology {
+    /// Every edge is shared by at most two triangles, with matching windings.
+    pub fn is_manifold(&self) -> bool {
+        self.non_manifold_edges == 0 && self.inconsistent_edges == 0
+    }
+
+    /// A manifold without holes, enclosing a volume.
+    pub fn is_watertight(&self) -> bool {
+        self.is_manifold() && self.boundary_edges == 0
+    }
+}
+
+impl CpuMesh {
+    /// Merges vertices closer than `tolerance`, keeping the first of each group. A tolerance of
+    /// zero merges identical positions only.
+    pub fn weld(&self, tolerance: f32) -> Self {
+        let cell = |pos: Vec3| {
+            if tolerance > 0.0 {
+                (
+                    (pos.x / tolerance).floor() as i64,
+                    (pos.y / tolerance).floor() as i64,
+                    (pos.z / tolerance).floor() as i64,
+                )
+            } else {
+                // Adding zero turns -0 into 0, which share no bits
+                let bits = |v: f32| (v + 0.0).to_bits() as i64;
+                (bits(pos.x), bits(pos.y), bits(pos.z))
+            }
+        };
+        let mut grid: HashMap<(i64, i64, i64), Vec<u32>> = HashMap::new();
+        let mut positions: Vec<Vec3> = Vec::new();
+        let mut kept = Vec::new();
+        let mut remap = Vec::with_capacity(self.positions.len());
+        for (i, &pos) in self.positions.iter().enumerate() {
+            let (x, y, z) = cell(pos);
+            // Close vertices can sit on either side of a cell border
+            let found = (-1..=1)
+                .flat_map(|dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| (dx, dy, dz))))
+                .filter_map(|(dx, dy, dz)| grid.get(&(x + dx, y + dy, z + dz)))
+                .flatten()
+                .find(|&&i| (positions[i as usize] - pos).magnitude() <= tolerance)
+                .copied();
+            let index = match found {
+                Some(index) => index,
+                None => {
+                    positions.push(pos);
+                    kept.push(i as u32);
+                    let index = positions.len() as u32 - 1;
+                    grid.entry((x, y, z)).or_default().push(index);
+                    index
+                }
+            };
+            remap.push(index);
+        }
+        let indices = self.indices.iter().map(|&i| remap[i as usize]).collect();
+        self.gather(&kept, indices)
+    }
+
+    /// Drops triangles with repeated corners or an area below `min_area`.
+    pub fn remove_degenerate(&self, min_area: f32) -> Self {
+        let indices = self
+            .indices
+            .chunks(3)
+            .filter(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| self.positions[i as usize]);
+                t[0] != t[1]
+                    && t[1] != t[2]
+                    && t[2] != t[0]
+                    && (b - a).cross(c - a).magnitude() / 2.0 >= min_area
+            })
+            .flatten()
+            .copied()
+            .collect();
+        Self {
+            positions: self.positions.clone(),
+            indices,
+            attributes: self.attributes.clone(),
+        }
+    }
+
+    /// Drops triangles using the same corners as an earlier one, whatever their winding.
+    pub fn remove_duplicates(&self) -> Self {
+        let mut seen = HashSet::new();
+        let indices = self
+            .indices
+            .chunks(3)
+            .filter(|t| {
+                let mut key = [t[0], t[1], t[2]];
+                key.sort_unstable();
+                seen.insert(key)
+            })
+            .flatten()
+            .copied()
+            .collect();
+        Self {
+            positions: self.positions.clone(),
+            indices,
+            attributes: self.attributes.clone(),
+        }
+    }
+
+    /// Drops vertices no triangle uses.
+    pub fn remove_unused(&self) -> Self {
+        let mut remap = vec![None; self.positions.len()];
+        let mut kept = Vec::new();
+        let indices = self
+            .indices
+            .iter()
+            .map(|&i| {
+                *remap[i as usize].get_or_insert_with(|| {
+                    kept.push(i);
+                    kept.len() as u32 - 1
+                })
+            })
+            .collect();
+        self.gather(&kept, indices)
+    }
+
+    /// Welds, then removes degenerate and duplicate triangles and unused vertices.
+    pub fn clean(&self, tolerance: f32) -> Self {
+        self.weld(tolerance)
+            .remove_degenerate(tolerance * tolerance)
+            .remove_duplicates()
+            .remove_unused()
+    }
+
+    /// Every edge, with the directions the triangles using it run along it.
+    fn edge_uses(&self) -> HashMap<(u32, u32), Vec<(u32, u32)>> {
+        let mut uses: HashMap<(u32, u32), Vec<(u32, u32)>> = HashMap::new();
+        for t in self.indices.chunks(3) {
+            for i in 0..3 {
+                let edge = (t[i], t[(i + 1) % 3]);
+                uses.entry(edge_key(edge.0, edge.1)).or_default().push(edge);
+            }
+        }
+        uses
+    }
+
+    /// Edges used by a single triangle, in the direction that triangle runs along them.
+    pub fn boundary_edges(&self) -> Vec<(u32, u32)> {
+        self.edge_uses()
+            .into_values()
+            .filter(|edges| edges.len() == 1)
+            .map(|edges| edges[0])
+            .collect()
+    }
+
+    /// Counts boundary, non-manifold and inconsistently wound edges. Vertices are compared by
+    /// index, so `weld` meshes built from separate pieces first.
+    pub fn topology(&self) -> Topology {
+        let uses = self.edge_uses();
+        let mut topology = Topology {
+            vertices: self.positions.len(),
+            triangles: self.indices.len() / 3,
+            boundary_edges: 0,
+            non_manifold_edges: 0,
+            inconsistent_edges: 0,
+        };
+        for edges in uses.values() {
+            match edges.as_slice() {
+                [_] => topology.boundary_edges += 1,
+                [a, b] if a == b => topology.inconsistent_edges += 1,
+                [_, _] => {}
+                _ => topology.non_manifold_edges += 1,
+            }
+        }
+        topology
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use three_d::vec3;
+
+    use super::*;
+    use crate::pre_embed::{cube, plane, revolution};
+
+    fn unit_plane(x: u32, y: u32) -> CpuMesh {
+        plane(x, y, Vec3::unit_x(), Vec3::unit_y(), vec3(0.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn plane_is_open() {
+        let mesh = unit_plane(3, 2);
+        let topology = mesh.topology();
+        assert_eq!(topology.vertices, 4 * 3);
+        assert_eq!(topology.triangles, 3 * 2 * 2);
+        assert_eq!(topology.boundary_edges, 2 * (3 + 2));
+        assert!(topology.is_manifold());
+        assert!(!topology.is_watertight());
+    }
+
+    #[test]
+    fn plane_boundary_is_a_loop() {
+        let mesh = unit_plane(3, 2);
+        let edges = mesh.boundary_edges();
+        assert_eq!(edges.len(), 10);
+        // Every corner on the rim starts one boundary edge and ends another
+        let starts: HashSet<u32> = edges.iter().map(|&(a, _)| a).collect();
+        let ends: HashSet<u32> = edges.iter().map(|&(_, b)| b).collect();
+        assert_eq!(starts.len(), 10);
+        assert_eq!(starts, ends);
+        for (a, b) in edges {
+            let (a, b) = (mesh.positions[a as usize], mesh.positions[b as usize]);
+            let on_rim = |p: Vec3| p.x == 0.0 || p.x == 1.0 || p.y == 0.0 || p.y == 1.0;
+            assert!(on_rim(a) && on_rim(b));
+        }
+    }
+
+    #[test]
+    fn cube_is_watertight() {
+        let topology = cube(2, 3, 4).topology();
+        // Grid points on the surface of a 3x4x5 lattice
+        assert_eq!(topology.vertices, 3 * 4 * 5 - 2 * 3);
+        assert_eq!(topology.triangles, 2 * 2 * (2 * 3 + 2 * 4 + 3 * 4));
+        assert!(topology.is_watertight());
+        assert!(cube(2, 3, 4).boundary_edges().is_empty());
+    }
+
+    #[test]
+    fn weld_joins_split_triangles() {
+        let split = cube(2, 2, 2).split_triangles();
+        assert_eq!(split.topology().boundary_edges, split.indices.len());
+        let welded = split.weld(1e-6);
+        assert_eq!(welded.positions.len(), 3 * 3 * 3 - 1);
+        assert!(welded.topology().is_watertight());
+    }
+
+    #[test]
+    fn weld_without_tolerance_joins_identical_vertices() {
+        let split = cube(2, 2, 2).split_triangles();
+        let welded = split.weld(0.0);
+        assert_eq!(welded.positions.len(), 3 * 3 * 3 - 1);
+        assert!(welded.topology().is_watertight());
+
+        let mesh = CpuMesh::new(
+            vec![
+                vec3(0.0, 0.0, 0.0),
+                vec3(-0.0, 0.0, 0.0),
+                vec3(1e-7, 0.0, 0.0),
+            ],
+            None,
+        );
+        assert_eq!(mesh.weld(0.0).positions.len(), 2);
+    }
+
+    #[test]
+    fn weld_keeps_distant_vertices() {
+        let mesh = unit_plane(4, 4);
+        assert_eq!(mesh.weld(0.1).positions.len(), mesh.positions.len());
+        // Every vertex is within reach of the first one
+        assert_eq!(mesh.weld(2.0).positions.len(), 1);
+    }
+
+    #[test]
+    fn clean_drops_broken_triangles() {
+        let mut mesh = cube(1, 1, 1);
+        let count = mesh.positions.len() as u32;
+        // A repeated triangle with flipped winding, a degenerate one and an unused vertex
+        let (a, b, c) = (mesh.indices[0], mesh.indices[1], mesh.indices[2]);
+        mesh.indices.extend(&[a, c, b, a, a, b]);
+        mesh.positions.push(vec3(2.0, 2.0, 2.0));
+        assert!(!mesh.topology().is_manifold());
+
+        let clean = mesh.clean(1e-6);
+        assert_eq!(clean.positions.len(), count as usize);
+        assert_eq!(clean.topology(), cube(1, 1, 1).topology());
+    }
+
+    #[test]
+    fn cylinder_is_watertight() {
+        let mesh = revolution(12, 3, 1, |t| (t, 1.0));
+        let topology = mesh.topology();
+        assert_eq!(topology.vertices, 13 * 4 * 2);
+        assert!(topology.is_watertight());
+        // Welding or cleaning a clean mesh changes nothing
+        assert_eq!(mesh.clean(1e-6).topology(), topology);
+    }
+}
@@ -0,0 +1,675 @@
+use std::collections::HashSet;
+
+use derive_more::Display;
+use palette::{FromColor, Lab, Srgb};
+use serde_json::{json, Map, Value};
+use three_d::{vec3, Vec3};
+
+use crate::{css, embed::oklab_to_srgb};
+
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum PaletteError {
+    #[display(fmt = "{}", _0)]
+    Parse(String),
+    #[display(fmt = "unsupported: {}", _0)]
+    Unsupported(String),
+    #[display(fmt = "unexpected end of file")]
+    Truncated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// GIMP `.gpl`.
+    Gpl,
+    /// Adobe Swatch Exchange `.ase`.
+    Ase,
+    /// Adobe Color Swatch `.aco`.
+    Aco,
+    /// CSS custom properties in a `:root` rule.
+    Css,
+    /// A Tailwind config with `theme.extend.colors`.
+    Tailwind,
+    /// An array of `{ "name", "hex" }` objects.
+    Json,
+}
+
+impl PaletteFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gpl" => Some(Self::Gpl),
+            "ase" => Some(Self::Ase),
+            "aco" => Some(Self::Aco),
+            "css" => Some(Self::Css),
+            "tailwind" => Some(Self::Tailwind),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedColor {
+    pub name: Option<String>,
+    /// sRGB in `0..1`.
+    pub srgb: Vec3,
+}
+
+impl NamedColor {
+    pub fn new(name: Option<String>, srgb: Vec3) -> Self {
+        Self { name, srgb }
+    }
+
+    fn bytes(&self) -> [u8; 3] {
+        [self.srgb.x, self.srgb.y, self.srgb.z].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// `#rrggbb`.
+    pub fn hex(&self) -> String {
+        let [r, g, b] = self.bytes();
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+
+    /// The name, or `color-<n>` for the `index`th color of a palette, usable as an identifier.
+    fn slug(&self, index: usize) -> String {
+        let slug: String = match &self.name {
+            Some(name) => name
+                .trim()
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || c == '-' || c == '_' {
+                        c.to_ascii_lowercase()
+                    } else {
+                        '-'
+                    }
+                })
+                .collect(),
+            None => String::new(),
+        };
+        if slug.is_empty() {
+            format!("color-{}", index + 1)
+        } else {
+            slug
+        }
+    }
+}
+
+/// Reads `#rgb`, `#rgba`, `#rrggbb` and `#rrggbbaa`, ignoring alpha.
+pub fn parse_hex(src: &str) -> Option<Vec3> {
+    let digits = src.trim().strip_prefix('#')?;
+    // Keeps the slicing below on character boundaries
+    if !digits.is_ascii() {
+        return None;
+    }
+    let value = |digits: &str| {
+        u8::from_str_radix(digits, 16)
+            .ok()
+            .map(|v| v as f32 / 255.0)
+    };
+    match digits.len() {
+        3 | 4 => {
+            let channel = |i: usize| value(&digits[i..i + 1].repeat(2));
+            Some(vec3(channel(0)?, channel(1)?, channel(2)?))
+        }
+        6 | 8 => {
+            let channel = |i: usize| value(&digits[i * 2..i * 2 + 2]);
+            Some(vec3(channel(0)?, channel(1)?, channel(2)?))
+        }
+        _ => None,
+    }
+}
+
+/// Named colors exchanged with design tools.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PaletteFile {
+    pub name: Option<String>,
+    pub colors: Vec<NamedColor>,
+}
+
+impl PaletteFile {
+    pub fn new(name: Option<String>, colors: Vec<NamedColor>) -> Self {
+        Self { name, colors }
+    }
+
+    /// `NamedColor::slug` of every color, with `-2`, `-3`, … appended to repeats.
+    fn slugs(&self) -> Vec<String> {
+        let mut taken = HashSet::new();
+        self.colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| {
+                let slug = color.slug(i);
+                let mut unique = slug.clone();
+                let mut count = 1;
+                while !taken.insert(unique.clone()) {
+                    count += 1;
+                    unique = format!("{}-{}", slug, count);
+                }
+                unique
+            })
+            .collect()
+    }
+
+    pub fn read(bytes: &[u8], format: PaletteFormat) -> Result<Self, PaletteError> {
+        let text =
+            || std::str::from_utf8(bytes).map_err(|error| PaletteError::Parse(error.to_string()));
+        match format {
+            PaletteFormat::Gpl => Self::from_gpl(text()?),
+            PaletteFormat::Ase => Self::from_ase(bytes),
+            PaletteFormat::Aco => Self::from_aco(bytes),
+            PaletteFormat::Css => Self::from_css(text()?),
+            PaletteFormat::Tailwind => Self::from_tailwind(text()?),
+            PaletteFormat::Json => Self::from_json(text()?),
+        }
+    }
+
+    pub fn write(&self, format: PaletteFormat) -> Vec<u8> {
+        match format {
+            PaletteFormat::Gpl => self.to_gpl().into_bytes(),
+            PaletteFormat::Ase => self.to_ase(),
+            PaletteFormat::Aco => self.to_aco(),
+            PaletteFormat::Css => self.to_css().into_bytes(),
+            PaletteFormat::Tailwind => self.to_tailwind().into_bytes(),
+            PaletteFormat::Json => self.to_json().into_bytes(),
+        }
+    }
+
+    pub fn from_gpl(src: &str) -> Result<Self, PaletteError> {
+        let mut lines = src.lines().enumerate();
+        match lines.next() {
+            Some((_, line)) if line.trim() == "GIMP Palette" => {}
+            _ => return Err(PaletteError::Parse("missing GIMP Palette header".into())),
+        }
+        let mut palette = Self::default();
+        for (number, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("Columns:") {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("Name:") {
+                palette.name = Some(name.trim().to_string());
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let mut channel = || -> Result<f32, PaletteError> {
+                let word = words.next().ok_or_else(|| {
+                    PaletteError::Parse(format!("line {}: expected r g b", number + 1))
+                })?;
+                word.parse::<u8>()
+                    .map(|value| value as f32 / 255.0)
+                    .map_err(|_| PaletteError::Parse(format!("line {}: {}", number + 1, word)))
+            };
+            let srgb = vec3(channel()?, channel()?, channel()?);
+            let name = words.collect::<Vec<_>>().join(" ");
+            let name = (!name.is_empty()).then_some(name);
+            palette.colors.push(NamedColor::new(name, srgb));
+        }
+        Ok(palette)
+    }
+
+    pub fn to_gpl(&self) -> String {
+        let mut src = String::from("GIMP Palette\n");
+        if let Some(name) = &self.name {
+            src.push_str(&format!("Name: {}\n", name));
+        }
+        src.push_str("#\n");
+        for (i, color) in self.colors.iter().enumerate() {
+            let [r, g, b] = color.bytes();
+            let name = color.name.clone().unwrap_or_else(|| color.slug(i));
+            src.push_str(&format!("{:3} {:3} {:3}\t{}\n", r, g, b, name));
+        }
+        src
+    }
+
+    pub fn from_ase(bytes: &[u8]) -> Result<Self, PaletteError> {
+        let mut reader = Reader(bytes);
+        if reader.take(4)? != b"ASEF" {
+            return Err(PaletteError::Parse("missing ASEF signature".into()));
+        }
+        reader.take(4)?;
+        let blocks = reader.u32()?;
+        let mut palette = Self::default();
+        for _ in 0..blocks {
+            let kind = reader.u16()?;
+            let length = reader.u32()? as usize;
+            let mut block = Reader(reader.take(length)?);
+            // Group starts carry a name, group ends nothing
+            if kind != 0x0001 {
+                if kind == 0xc001 && palette.name.is_none() {
+                    palette.name = block.utf16().ok().filter(|name| !name.is_empty());
+                }
+                continue;
+            }
+            let name = block.utf16()?;
+            let model = block.take(4)?;
+            let srgb = match model {
+                b"RGB " => vec3(block.f32()?, block.f32()?, block.f32()?),
+                b"Gray" => {
+                    let gray = block.f32()?;
+                    vec3(gray, gray, gray)
+                }
+                b"CMYK" => {
+                    let [c, m, y, k] = [block.f32()?, block.f32()?, block.f32()?, block.f32()?];
+                    cmyk(c, m, y, k)
+                }
+                b"LAB " => lab(block.f32()? * 100.0, block.f32()?, block.f32()?),
+                _ => {
+                    let model = String::from_utf8_lossy(model).to_string();
+                    return Err(PaletteError::Unsupported(format!(
+                        "ASE color model {}",
+                        model
+                    )));
+                }
+            };
+            let name = (!name.is_empty()).then_some(name);
+            palette.colors.push(NamedColor::new(name, srgb));
+        }
+        Ok(palette)
+    }
+
+    pub fn to_ase(&self) -> Vec<u8> {
+        let mut bytes = b"ASEF".to_vec();
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend(0u16.to_be_bytes());
+        bytes.extend((self.colors.len() as u32).to_be_bytes());
+        for (i, color) in self.colors.iter().enumerate() {
+            let mut block = utf16(&color.name.clone().unwrap_or_else(|| color.slug(i)));
+            block.extend(b"RGB ");
+            for c in [color.srgb.x, color.srgb.y, color.srgb.z] {
+                block.extend(c.clamp(0.0, 1.0).to_be_bytes());
+            }
+            // A global color
+            block.extend(0u16.to_be_bytes());
+            bytes.extend(0x0001u16.to_be_bytes());
+            bytes.extend((block.len() as u32).to_be_bytes());
+            bytes.extend(block);
+        }
+        bytes
+    }
+
+    /// Reads the named version 2 section when there is one, the version 1 section otherwise.
+    pub fn from_aco(bytes: &[u8]) -> Result<Self, PaletteError> {
+        let mut reader = Reader(bytes);
+        let mut palette = Self::default();
+        while !reader.0.is_empty() {
+            let version = reader.u16()?;
+            if version != 1 && version != 2 {
+                return Err(PaletteError::Unsupported(format!(
+                    "ACO version {}",
+                    version
+                )));
+            }
+            let count = reader.u16()?;
+            let mut colors = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let space = reader.u16()?;
+                let [w, x, y, z] = [reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?];
+                let srgb = aco_color(space, [w, x, y, z])?;
+                let name = if version == 2 {
+                    // The length counts UTF-16 units, the terminator included
+                    let length = reader.u32()? as usize;
+                    let name = decode_utf16(reader.take(length * 2)?);
+                    (!name.is_empty()).then_some(name)
+                } else {
+                    None
+                };
+                colors.push(NamedColor::new(name, srgb));
+            }
+            palette.colors = colors;
+            if version == 2 {
+                break;
+            }
+        }
+        Ok(palette)
+    }
+
+    /// Writes a version 1 section followed by a version 2 section with names.
+    pub fn to_aco(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for version in [1u16, 2] {
+            bytes.extend(version.to_be_bytes());
+            bytes.extend((self.colors.len() as u16).to_be_bytes());
+            for (i, color) in self.colors.iter().enumerate() {
+                // RGB, with each channel in `0..=65535` and an unused fourth value
+                bytes.extend(0u16.to_be_bytes());
+                for c in [color.srgb.x, color.srgb.y, color.srgb.z, 0.0] {
+                    bytes.extend(((c.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes());
+                }
+                if version == 2 {
+                    let name = color.name.clone().unwrap_or_else(|| color.slug(i));
+                    let units: Vec<u16> = name.encode_utf16().chain([0]).collect();
+                    bytes.extend((units.len() as u32).to_be_bytes());
+                    bytes.extend(units.iter().flat_map(|unit| unit.to_be_bytes()));
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Reads every `--name: color;` declaration whose value is a CSS color.
+    pub fn from_css(src: &str) -> Result<Self, PaletteError> {
+        let mut palette = Self::default();
+        for declaration in src.split([';', '{', '}']) {
+            let declaration = declaration.trim();
+            let Some((name, value)) = declaration.split_once(':') else {
+                continue;
+            };
+            let Some(name) = name.trim().strip_prefix("--") else {
+                continue;
+            };
+            if let Ok(color) = css::parse(value) {
+                let srgb = oklab_to_srgb(color.oklab);
+                palette
+                    .colors
+                    .push(NamedColor::new(Some(name.to_string()), srgb));
+            }
+        }
+        Ok(palette)
+    }
+
+    pub fn to_css(&self) -> String {
+        let mut src = String::from(":root {\n");
+        for (slug, color) in self.slugs().iter().zip(&self.colors) {
+            src.push_str(&format!("  --{}: {};\n", slug, color.hex()));
+        }
+        src.push_str("}\n");
+        src
+    }
+
+    /// Reads `theme.extend.colors`, `theme.colors` or top level `colors`. Nested shades are
+    /// named `<color>-<shade>`, and `DEFAULT` shades just `<color>`.
+    pub fn from_tailwind(src: &str) -> Result<Self, PaletteError> {
+        let config: Value =
+            serde_json::from_str(src).map_err(|error| PaletteError::Parse(error.to_string()))?;
+        let colors = [
+            &config["theme"]["extend"]["colors"],
+            &config["theme"]["colors"],
+            &config["colors"],
+        ]
+        .into_iter()
+        .find_map(Value::as_object)
+        .ok_or_else(|| PaletteError::Parse("no colors in the config".into()))?;
+        let mut palette = Self::default();
+        fn flatten(prefix: &str, colors: &Map<String, Value>, palette: &mut PaletteFile) {
+            for (key, value) in colors {
+                let name = match (prefix, key.as_str()) {
+                    (prefix, "DEFAULT") => prefix.to_string(),
+                    ("", key) => key.to_string(),
+                    (prefix, key) => format!("{}-{}", prefix, key),
+                };
+                match value {
+                    Value::String(value) => {
+                        if let Some(srgb) = parse_hex(value) {
+                            palette.colors.push(NamedColor::new(Some(name), srgb));
+                        }
+                    }
+                    Value::Object(shades) => flatten(&name, shades, palette),
+                    _ => {}
+                }
+            }
+        }
+        flatten("", colors, &mut palette);
+        Ok(palette)
+    }
+
+    pub fn to_tailwind(&self) -> String {
+        // Maps keep their insertion order with the `preserve_order` feature of serde_json
+        let colors: Map<String, Value> = self
+            .slugs()
+            .into_iter()
+            .zip(&self.colors)
+            .map(|(slug, color)| (slug, Value::String(color.hex())))
+            .collect();
+        let config = json!({ "theme": { "extend": { "colors": colors } } });
+        serde_json::to_string_pretty(&config).unwrap_or_default()
+    }
+
+    /// Reads an array of `{ "name", "hex" }` objects or plain hex strings.
+    pub fn from_json(src: &str) -> Result<Self, PaletteError> {
+        let value: Value =
+            serde_json::from_str(src).map_err(|error| PaletteError::Parse(error.to_string()))?;
+        let entries = value
+            .as_array()
+            .ok_or_else(|| PaletteError::Parse("expected an array of colors".into()))?;
+        let colors = entries
+            .iter()
+            .map(|entry| {
+                let (name, hex) = match entry {
+                    Value::String(hex) => (None, hex.as_str()),
+                    Value::Object(entry) => (
+                        entry
+                            .get("name")
+                            .and_then(Value::as_str)
+                            .map(str::to_string),
+                        entry.get("hex").and_then(Value::as_str).unwrap_or_default(),
+                    ),
+                    _ => (None, ""),
+                };
+                parse_hex(hex)
+                    .map(|srgb| NamedColor::new(name, srgb))
+                    .ok_or_else(|| PaletteError::Parse(format!("not a hex color: {}", entry)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(None, colors))
+    }
+
+    pub fn to_json(&self) -> String {
+        let entries: Vec<Value> = self
+            .colors
+            .iter()
+            .map(|color| json!({ "name": color.name, "hex": color.hex() }))
+            .collect();
+        serde_json::to_string_pretty(&entries).unwrap_or_default()
+    }
+}
+
+/// sRGB of a naive CMYK mix, each channel in `0..1`.
+fn cmyk(c: f32, m: f32, y: f32, k: f32) -> Vec3 {
+    vec3(
+        (1.0 - c) * (1.0 - k),
+        (1.0 - m) * (1.0 - k),
+        (1.0 - y) * (1.0 - k),
+    )
+}
+
+/// sRGB of a D65 CIELAB color, clamped into gamut.
+fn lab(l: f32, a: f32, b: f32) -> Vec3 {
+    let srgb = Srgb::from_color(Lab::new(l, a, b));
+    vec3(srgb.red, srgb.green, srgb.blue).map(|c| c.clamp(0.0, 1.0))
+}
+
+/// sRGB of an ACO color in `space`, whose values are in 16-bit units.
+fn aco_color(space: u16, [w, x, y, z]: [u16; 4]) -> Result<Vec3, PaletteError> {
+    let unit = |v: u16| v as f32 / 65535.0;
+    Ok(match space {
+        0 => vec3(unit(w), unit(x), unit(y)),
+        1 => {
+            let (h, s, v) = (unit(w) * 6.0, unit(x), unit(y));
+            let f = |n: f32| {
+                let k = (n + h) % 6.0;
+                v - v * s * k.min(4.0 - k).clamp(0.0, 1.0)
+            };
+            vec3(f(5.0), f(3.0), f(1.0))
+        }
+        // Ink coverage is stored inverted, 0 for full ink
+        2 => cmyk(1.0 - unit(w), 1.0 - unit(x), 1.0 - unit(y), 1.0 - unit(z)),
+        7 => lab(
+            w as f32 / 100.0,
+            x as i16 as f32 / 100.0,
+            y as i16 as f32 / 100.0,
+        ),
+        8 => {
+            let gray = 1.0 - w as f32 / 10000.0;
+            vec3(gray, gray, gray)
+        }
+        _ => {
+            return Err(PaletteError::Unsupported(format!(
+                "ACO color space {}",
+                space
+            )))
+        }
+    })
+}
+
+/// Big-endian fields read off the front of a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], PaletteError> {
+        if self.0.len() < count {
+            return Err(PaletteError::Truncated);
+        }
+        let (taken, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16, PaletteError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, PaletteError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, PaletteError> {
+        let bytes = self.take(4)?;
+        Ok(f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A string prefixed by its length in UTF-16 units, the terminator included.
+    fn utf16(&mut self) -> Result<String, PaletteError> {
+        let length = self.u16()? as usize;
+        Ok(decode_utf16(self.take(length * 2)?))
+    }
+}
+
+/// Big-endian UTF-16, up to the first terminator.
+fn decode_utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// `text` as a length-prefixed, terminated big-endian UTF-16 string.
+fn utf16(text: &str) -> Vec<u8> {
+    let units: Vec<u16> = text.encode_utf16().chain([0]).collect();
+    let mut bytes = (units.len() as u16).to_be_bytes().to_vec();
+    bytes.extend(units.iter().flat_map(|unit| unit.to_be_bytes()));
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [PaletteFormat; 6] = [
+        PaletteFormat::Gpl,
+        PaletteFormat::Ase,
+        PaletteFormat::Aco,
+        PaletteFormat::Css,
+        PaletteFormat::Tailwind,
+        PaletteFormat::Json,
+    ];
+
+    /// Colors on whole 8-bit steps, named so every format keeps the names as they are.
+    fn palette() -> PaletteFile {
+        let color = |name: &str, [r, g, b]: [u8; 3]| {
+            let srgb = vec3(r as f32, g as f32, b as f32) / 255.0;
+            NamedColor::new(Some(name.to_string()), srgb)
+        };
+        PaletteFile::new(
+            None,
+            vec![
+                color("red", [255, 0, 0]),
+                color("sky-blue", [51, 170, 238]),
+                color("ink", [17, 17, 34]),
+                color("white", [255, 255, 255]),
+                color("amber", [255, 191, 0]),
+            ],
+        )
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let palette = palette();
+        for format in FORMATS {
+            let read = PaletteFile::read(&palette.write(format), format)
+                .unwrap_or_else(|error| panic!("{:?}: {}", format, error));
+            assert_eq!(read.colors.len(), palette.colors.len(), "{:?}", format);
+            for (read, color) in read.colors.iter().zip(&palette.colors) {
+                assert_eq!(read.name, color.name, "{:?}", format);
+                let error = (read.srgb - color.srgb).map(f32::abs);
+                assert!(error.x.max(error.y).max(error.z) < 1e-3, "{:?}", format);
+                assert_eq!(read.hex(), color.hex(), "{:?}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn gpl_keeps_the_palette_name() {
+        let mut palette = palette();
+        palette.name = Some("Warm".to_string());
+        let read = PaletteFile::from_gpl(&palette.to_gpl()).unwrap();
+        assert_eq!(read.name.as_deref(), Some("Warm"));
+    }
+
+    #[test]
+    fn identifiers_are_unique() {
+        let gray = vec3(0.5, 0.5, 0.5);
+        let palette = PaletteFile::new(
+            None,
+            vec![
+                NamedColor::new(Some("Red".to_string()), gray),
+                NamedColor::new(Some("red".to_string()), gray),
+                NamedColor::new(Some("red-2".to_string()), gray),
+                NamedColor::new(None, gray),
+            ],
+        );
+        let names = ["red", "red-2", "red-2-2", "color-4"];
+        for format in [PaletteFormat::Css, PaletteFormat::Tailwind] {
+            let read = PaletteFile::read(&palette.write(format), format).unwrap();
+            let read: Vec<_> = read
+                .colors
+                .iter()
+                .filter_map(|c| c.name.as_deref())
+                .collect();
+            assert_eq!(read, names, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn parses_hex() {
+        let red = Some(vec3(1.0, 0.0, 0.0));
+        assert_eq!(parse_hex("#f00"), red);
+        assert_eq!(parse_hex(" #ff0000 "), red);
+        assert_eq!(parse_hex("#ff0000cc"), red);
+        assert_eq!(parse_hex("ff0000"), None);
+        assert_eq!(parse_hex("#f00c"), red);
+        assert_eq!(parse_hex("#éa"), None);
+        assert_eq!(parse_hex("#aéaaa"), None);
+        assert_eq!(parse_hex("#gg0000"), None);
+    }
+
+    #[test]
+    fn reads_css_named_colors() {
+        let src = ":root {\n  --brand: red;\n  --ink: rebeccapurple;\n  --skip: nonsense;\n}\n";
+        let read = PaletteFile::from_css(src).unwrap();
+        let read: Vec<_> = read
+            .colors
+            .iter()
+            .map(|color| (color.name.as_deref(), color.hex()))
+            .collect();
+        assert_eq!(
+            read,
+            [
+                (Some("brand"), "#ff0000".to_string()),
+                (Some("ink"), "#663399".to_string())
+            ]
+        );
+    }
+}
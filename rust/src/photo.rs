@@ -0,0 +1,56 @@
+use derive_more::Display;
+use three_d::{vec3, Vec3};
+
+use crate::embed::srgb_to_oklab;
+
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum PhotoError {
+    #[display(fmt = "could not decode image: {}", _0)]
+    Decode(String),
+    #[display(fmt = "image has no pixels")]
+    Empty,
+}
+
+/// A decoded image, kept as Oklab colors.
+pub struct Photo {
+    pub width: u32,
+    pub height: u32,
+    /// One color per pixel, row by row from the top left.
+    pub colors: Vec<Vec3>,
+}
+
+impl Photo {
+    /// Reads PNG or JPEG bytes, guessing the format from their signature.
+    pub fn decode(bytes: &[u8]) -> Result<Self, PhotoError> {
+        let image = image::load_from_memory(bytes)
+            .map_err(|error| PhotoError::Decode(error.to_string()))?
+            .to_rgb8();
+        if image.width() == 0 || image.height() == 0 {
+            return Err(PhotoError::Empty);
+        }
+        let colors = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b] = pixel.0.map(|c| c as f32 / 255.0);
+                srgb_to_oklab(vec3(r, g, b))
+            })
+            .collect();
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            colors,
+        })
+    }
+
+    /// Colors of pixels on an even grid, about `max` of them.
+    pub fn sample(&self, max: usize) -> Vec<Vec3> {
+        let stride = (self.colors.len() as f32 / max.max(1) as f32).sqrt().ceil() as usize;
+        let stride = stride.max(1);
+        let width = self.width as usize;
+        (0..self.height as usize)
+            .step_by(stride)
+            .flat_map(|y| (0..width).step_by(stride).map(move |x| y * width + x))
+            .map(|i| self.colors[i])
+            .collect()
+    }
+}
@@ -29,7 +29,7 @@ pub fn plane(
             let b = a + 1;
             let c = a + stride;
             let d = c + 1;
-            indices.extend(&[a as u32, b as u32, c as u32, b as u32, d as u32, c as u32]);
+            indices.extend(&[a, b, c, b, d, c]);
         }
     }
 
@@ -86,68 +86,121 @@ pub fn cube(
         corner,
     ));
     cube.face_away(vec3(0.5, 0.5, 0.5));
-    cube
+    // The faces share their edge vertices
+    cube.weld(1e-6)
 }
 
-/// Pre-embedded cylinder, where x indicates turn, y indicates height, and z indicates radius.
-pub fn _cylinder(
+/// Pre-embedded solid of revolution for cylindrical embeddings, where x indicates turn, y
+/// indicates height, and z indicates radius.
+///
+/// The cube is bent so `profile` maps its y in `0..1` to a height and the outer radius there.
+/// The x faces stay as cut faces at turn 0 and 1, which a wedge cutaway opens up.
+pub fn revolution<F>(
     horizontal_subdivisions: u32,
     vertical_subdivisions: u32,
     radial_subdivisions: u32,
-) -> CpuMesh {
-    let mut positions = Vec::new();
-    let mut indices = Vec::new();
-    let stride = radial_subdivisions * 2 + vertical_subdivisions + 1;
+    profile: F,
+) -> CpuMesh
+where
+    F: Fn(f32) -> (f32, f32),
+{
+    let mut mesh = cube(
+        horizontal_subdivisions,
+        vertical_subdivisions,
+        radial_subdivisions,
+    );
+    for pos in mesh.positions.iter_mut() {
+        let (height, radius) = profile(pos.y);
+        *pos = vec3(pos.x, height, pos.z * radius);
+    }
+    // Faces shrunk to the axis or a tip disappear
+    mesh.clean(1e-6)
+}
 
-    for hdiv in 0..horizontal_subdivisions + 1 {
-        let h = hdiv as f32 / horizontal_subdivisions as f32;
-        positions.extend(
-            (0..vertical_subdivisions + 1)
-                .map(|vdiv| vec3(h, vdiv as f32 / vertical_subdivisions as f32, 1.0)),
-        );
+/// Pre-embedded shapes to draw as the color solid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    Cube,
+    /// Full radius at every height, the same input positions as the cube.
+    Cylinder,
+    /// HSV-style, with the tip at the bottom.
+    Cone,
+    /// HSL-style, widest halfway up.
+    Bicone,
+    Sphere,
+    /// Dome over a full disc at the bottom.
+    Hemisphere,
+}
 
-        for rdiv in 0..radial_subdivisions {
-            let r = rdiv as f32 / radial_subdivisions as f32;
-            positions.extend(&[vec3(h, 0.0, r), vec3(h, 1.0, r)]);
+impl Primitive {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cube" => Some(Self::Cube),
+            "cylinder" => Some(Self::Cylinder),
+            "cone" => Some(Self::Cone),
+            "bicone" => Some(Self::Bicone),
+            "sphere" => Some(Self::Sphere),
+            "hemisphere" => Some(Self::Hemisphere),
+            _ => None,
         }
     }
 
-    for hdiv in 0..horizontal_subdivisions {
-        for vdiv in 0..vertical_subdivisions {
-            let a = hdiv * stride + vdiv;
-            let b = a + 1;
-            let c = a + stride;
-            let d = c + 1;
-            indices.extend(&[a as u32, b as u32, c as u32, b as u32, d as u32, c as u32]);
+    pub fn mesh(&self) -> CpuMesh {
+        use std::f32::consts::PI;
+        match self {
+            Self::Cube => cube(12, 3, 1),
+            Self::Cylinder => revolution(12, 3, 1, |t| (t, 1.0)),
+            Self::Cone => revolution(12, 3, 1, |t| (t, t)),
+            // An even number of rows puts a vertex ring at the rim
+            Self::Bicone => revolution(12, 4, 1, |t| (t, 1.0 - (2.0 * t - 1.0).abs())),
+            Self::Sphere => {
+                revolution(12, 8, 1, |t| ((1.0 - (PI * t).cos()) / 2.0, (PI * t).sin()))
+            }
+            Self::Hemisphere => {
+                revolution(12, 4, 1, |t| ((PI / 2.0 * t).sin(), (PI / 2.0 * t).cos()))
+            }
         }
-        for rdiv in 0..radial_subdivisions - 1 {
-            let a = hdiv * stride + vertical_subdivisions + 1 + rdiv * 2;
-            let b = a + 2;
-            let c = a + stride;
-            let d = c + 2;
-            let i = [a as u32, b as u32, c as u32, b as u32, d as u32, c as u32];
-            indices.extend(&i);
-            indices.extend(i.map(|i| i + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives_are_indexed_and_closed() {
+        // A 13x(rows + 1) grid on each radial face, less a ring wherever the radius is zero
+        let expected = [
+            ("cube", 13 * 4 * 2),
+            ("cylinder", 13 * 4 * 2),
+            ("cone", 13 * 4 * 2 - 13),
+            ("bicone", 13 * 5 * 2 - 2 * 13),
+            ("sphere", 13 * 9 * 2 - 2 * 13),
+            ("hemisphere", 13 * 5 * 2 - 13),
+        ];
+        for (name, vertices) in expected {
+            let mesh = Primitive::from_name(name).unwrap().mesh();
+            let topology = mesh.topology();
+            assert_eq!(topology.vertices, vertices, "{}", name);
+            assert!(mesh.indices.len() > mesh.positions.len(), "{}", name);
+            assert!(
+                mesh.indices.iter().all(|&i| (i as usize) < vertices),
+                "{}",
+                name
+            );
+            assert_eq!(topology.boundary_edges, 0, "{}", name);
+            assert!(topology.is_watertight(), "{}", name);
         }
-        // Connect radial subdivisions to vertical subdivisions
-        // bottom radial 1
-        let ab = (hdiv + 1) * stride - 2;
-        // bottom vertical 1
-        let bb = hdiv * stride;
-        // bottom radial 2
-        let cb = (hdiv + 2) * stride - 2;
-        // bottom vertical 2
-        let db = (hdiv + 1) * stride;
-        // top radial 1
-        let at = ab + 1;
-        // top vertical 1
-        let bt = bb + vertical_subdivisions;
-        // top radial 2
-        let ct = cb + 1;
-        // top vertical 2
-        let dt = db + vertical_subdivisions;
-        indices.extend(&[ab, bb, cb, bb, db, cb, at, bt, ct, bt, dt, ct]);
     }
 
-    CpuMesh::new(positions, Some(indices))
+    #[test]
+    fn profiles_stay_in_the_unit_cylinder() {
+        for name in ["cylinder", "cone", "bicone", "sphere", "hemisphere"] {
+            let mesh = Primitive::from_name(name).unwrap().mesh();
+            for pos in &mesh.positions {
+                assert!((0.0..=1.0).contains(&pos.y), "{} {:?}", name, pos);
+                assert!((-1e-6..=1.0 + 1e-6).contains(&pos.z), "{} {:?}", name, pos);
+            }
+        }
+    }
 }
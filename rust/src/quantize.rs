@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use cgmath::{ElementWise, InnerSpace};
+use three_d::{vec3, Vec3};
+
+use crate::embed::Embedding;
+
+/// How colors are grouped into a few representative ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantizer {
+    /// Lloyd iterations from a k-means++ start. Slowest, with the tightest clusters.
+    KMeans,
+    /// Splits the most spread out box of colors at its median until there are enough boxes.
+    MedianCut,
+    /// Merges the sparsest cells of an octree until there are few enough.
+    Octree,
+}
+
+impl Quantizer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "kmeans" | "k-means" => Some(Self::KMeans),
+            "median-cut" => Some(Self::MedianCut),
+            "octree" => Some(Self::Octree),
+            _ => None,
+        }
+    }
+}
+
+/// A representative color and the fraction of the colors it stands for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Swatch {
+    /// Oklab.
+    pub color: Vec3,
+    pub weight: f32,
+}
+
+/// Groups Oklab `colors` into at most `count` swatches, heaviest first.
+///
+/// Distances are measured between the inputs of `embedding`, so an `IdentityEmbedding` groups
+/// them in Oklab. Periodic axes are cut open at their widest arc without colors, so colors on
+/// both sides of the seam, like reds around hue 0, group and average together.
+pub fn dominant_colors(
+    colors: &[Vec3],
+    count: usize,
+    quantizer: Quantizer,
+    embedding: &dyn Embedding<Vec3>,
+) -> Vec<Swatch> {
+    if colors.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let mut points: Vec<Vec3> = colors
+        .iter()
+        .map(|&color| embedding.invert(color))
+        .collect();
+    let axes = embedding.axes();
+    let turns = open_periodic(&mut points, axes.map(|axis| axis.periodic));
+    let clusters = match quantizer {
+        Quantizer::KMeans => k_means(&points, count),
+        Quantizer::MedianCut => median_cut(&points, count),
+        Quantizer::Octree => octree(&points, count),
+    };
+    // Turns the centers back
+    let close = |mut center: Vec3| {
+        center += turns;
+        for axis in (0..3).filter(|&axis| axes[axis].periodic) {
+            center[axis] = center[axis].rem_euclid(1.0);
+        }
+        center
+    };
+    let total = points.len() as f32;
+    let mut swatches: Vec<Swatch> = clusters
+        .into_iter()
+        .filter(|&(_, size)| size > 0)
+        .map(|(center, size)| Swatch {
+            color: embedding.embed(close(center)),
+            weight: size as f32 / total,
+        })
+        .collect();
+    swatches.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+    swatches
+}
+
+/// Turns every periodic axis so its widest empty arc starts at 0, leaving the points in `0..1`
+/// on that axis. Returns the turn to add back to every axis.
+fn open_periodic(points: &mut [Vec3], periodic: [bool; 3]) -> Vec3 {
+    let mut turns = vec3(0.0, 0.0, 0.0);
+    for axis in (0..3).filter(|&axis| periodic[axis]) {
+        let mut values: Vec<f32> = points.iter().map(|p| p[axis].rem_euclid(1.0)).collect();
+        values.sort_by(f32::total_cmp);
+        // The arc from the last value around to the first one is a candidate too
+        let mut start = values[0];
+        let mut widest = values[0] + 1.0 - values[values.len() - 1];
+        for pair in values.windows(2) {
+            if pair[1] - pair[0] > widest {
+                widest = pair[1] - pair[0];
+                start = pair[1];
+            }
+        }
+        for p in points.iter_mut() {
+            p[axis] = (p[axis] - start).rem_euclid(1.0);
+        }
+        turns[axis] = start;
+    }
+    turns
+}
+
+fn mean(points: &[Vec3]) -> Vec3 {
+    points.iter().fold(vec3(0.0, 0.0, 0.0), |sum, &p| sum + p) / points.len().max(1) as f32
+}
+
+/// Index of the closest of `centers` to `point`.
+fn closest(centers: &[Vec3], point: Vec3) -> usize {
+    (0..centers.len())
+        .min_by(|&a, &b| {
+            (centers[a] - point)
+                .magnitude2()
+                .total_cmp(&(centers[b] - point).magnitude2())
+        })
+        .unwrap_or(0)
+}
+
+/// A fixed xorshift sequence in `0..1`, so the same image always gives the same swatches.
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32
+    }
+}
+
+const K_MEANS_PASSES: usize = 24;
+
+fn k_means(points: &[Vec3], count: usize) -> Vec<(Vec3, usize)> {
+    let mut random = Random(0x9e37_79b9);
+    // k-means++: every next center is drawn with probability growing with squared distance
+    let mut centers = vec![points[0]];
+    let mut distances: Vec<f32> = points
+        .iter()
+        .map(|&p| (p - points[0]).magnitude2())
+        .collect();
+    while centers.len() < count.min(points.len()) {
+        let total: f32 = distances.iter().sum();
+        if total <= 0.0 {
+            break;
+        }
+        let mut target = random.next() * total;
+        let next = distances
+            .iter()
+            .position(|&distance| {
+                target -= distance;
+                target <= 0.0
+            })
+            .unwrap_or(points.len() - 1);
+        centers.push(points[next]);
+        for (distance, &p) in distances.iter_mut().zip(points) {
+            *distance = distance.min((p - points[next]).magnitude2());
+        }
+    }
+
+    let mut assignment = vec![0; points.len()];
+    for _ in 0..K_MEANS_PASSES {
+        let mut moved = false;
+        for (assigned, &p) in assignment.iter_mut().zip(points) {
+            let center = closest(&centers, p);
+            moved |= *assigned != center;
+            *assigned = center;
+        }
+        let mut sums = vec![(vec3(0.0, 0.0, 0.0), 0); centers.len()];
+        for (&center, &p) in assignment.iter().zip(points) {
+            sums[center].0 += p;
+            sums[center].1 += 1;
+        }
+        for (center, (sum, size)) in centers.iter_mut().zip(&sums) {
+            if *size > 0 {
+                *center = *sum / *size as f32;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+    let mut sizes = vec![0; centers.len()];
+    for &center in &assignment {
+        sizes[center] += 1;
+    }
+    centers.into_iter().zip(sizes).collect()
+}
+
+fn median_cut(points: &[Vec3], count: usize) -> Vec<(Vec3, usize)> {
+    let extent = |points: &[Vec3]| {
+        let low = points
+            .iter()
+            .fold(points[0], |low, p| low.zip(*p, f32::min));
+        let high = points
+            .iter()
+            .fold(points[0], |high, p| high.zip(*p, f32::max));
+        high - low
+    };
+    let mut boxes = vec![points.to_vec()];
+    while boxes.len() < count {
+        // The box with the most colors times its longest side is split next
+        let widest = (0..boxes.len())
+            .filter(|&i| boxes[i].len() > 1)
+            .max_by(|&a, &b| {
+                let score = |i: usize| {
+                    let extent = extent(&boxes[i]);
+                    extent.x.max(extent.y).max(extent.z) * boxes[i].len() as f32
+                };
+                score(a).total_cmp(&score(b))
+            });
+        let mut split = match widest {
+            Some(i) => boxes.swap_remove(i),
+            None => break,
+        };
+        let sides = extent(&split);
+        let axis = if sides.x >= sides.y && sides.x >= sides.z {
+            0
+        } else if sides.y >= sides.z {
+            1
+        } else {
+            2
+        };
+        split.sort_by(|a, b| a[axis].total_cmp(&b[axis]));
+        let upper = split.split_off(split.len() / 2);
+        boxes.push(split);
+        boxes.push(upper);
+    }
+    boxes
+        .iter()
+        .map(|points| (mean(points), points.len()))
+        .collect()
+}
+
+/// Levels of the octree below its root.
+const OCTREE_DEPTH: u32 = 6;
+
+/// The summed colors and count of a parent cell, and the keys of the children merged into it.
+type Merge = (Vec3, usize, Vec<u64>);
+
+fn octree(points: &[Vec3], count: usize) -> Vec<(Vec3, usize)> {
+    let low = points
+        .iter()
+        .fold(points[0], |low, p| low.zip(*p, f32::min));
+    let high = points
+        .iter()
+        .fold(points[0], |high, p| high.zip(*p, f32::max));
+    let size = (high - low).map(|side| side.max(1e-6));
+    let cells = (1 << OCTREE_DEPTH) as f32;
+    // Leaves keyed by level and the interleaved bits of their cell
+    let mut leaves: HashMap<(u32, u64), (Vec3, usize)> = HashMap::new();
+    for &p in points {
+        let cell = (p - low)
+            .div_element_wise(size)
+            .map(|t| ((t * cells) as u64).min(cells as u64 - 1));
+        let mut key = 0;
+        for bit in (0..OCTREE_DEPTH).rev() {
+            key = key << 3
+                | ((cell.x >> bit) & 1) << 2
+                | ((cell.y >> bit) & 1) << 1
+                | ((cell.z >> bit) & 1);
+        }
+        let leaf = leaves
+            .entry((OCTREE_DEPTH, key))
+            .or_insert((vec3(0.0, 0.0, 0.0), 0));
+        leaf.0 += p;
+        leaf.1 += 1;
+    }
+
+    // Deepest leaves merge first, those holding the fewest colors before the others
+    for level in (1..=OCTREE_DEPTH).rev() {
+        if leaves.len() <= count {
+            break;
+        }
+        let mut parents: HashMap<u64, Merge> = HashMap::new();
+        for (&(_, key), &(sum, size)) in leaves.iter().filter(|((l, _), _)| *l == level) {
+            let parent = parents
+                .entry(key >> 3)
+                .or_insert((vec3(0.0, 0.0, 0.0), 0, Vec::new()));
+            parent.0 += sum;
+            parent.1 += size;
+            parent.2.push(key);
+        }
+        let mut parents: Vec<(u64, Merge)> = parents.into_iter().collect();
+        parents.sort_by_key(|(key, (_, size, _))| (*size, *key));
+        for (key, (sum, size, children)) in parents {
+            if leaves.len() <= count {
+                break;
+            }
+            for child in children {
+                leaves.remove(&(level, child));
+            }
+            leaves.insert((level - 1, key), (sum, size));
+        }
+    }
+    leaves
+        .into_values()
+        .map(|(sum, size)| (sum / size as f32, size))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embed::OkhsvEmbedding;
+
+    #[test]
+    fn groups_hues_across_the_seam() {
+        let embedding = OkhsvEmbedding {};
+        let colors: Vec<Vec3> = [0.01, 0.02, 0.98, 0.99]
+            .iter()
+            .map(|&hue| embedding.embed(vec3(hue, 0.8, 0.8)))
+            .collect();
+        for quantizer in [Quantizer::KMeans, Quantizer::MedianCut, Quantizer::Octree] {
+            let swatches = dominant_colors(&colors, 1, quantizer, &embedding);
+            assert_eq!(swatches.len(), 1);
+            let hue = embedding.invert(swatches[0].color).x;
+            // Averaged linearly the reds would turn cyan, at hue 0.5
+            let distance = hue.min(1.0 - hue);
+            assert!(distance < 0.02, "{:?} hue {}", quantizer, hue);
+        }
+    }
+}
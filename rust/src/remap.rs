@@ -0,0 +1,317 @@
+use cgmath::InnerSpace;
+use palette::{color_difference::Ciede2000, FromColor, Lab, Oklab};
+use three_d::{vec3, Vec3};
+
+use crate::{embed::oklab_to_srgb, photo::Photo};
+
+/// How the difference between two colors is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaE {
+    /// Euclidean distance in Oklab.
+    Ok,
+    /// Euclidean distance in CIELAB.
+    Cie76,
+    Ciede2000,
+}
+
+impl DeltaE {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ok" => Some(Self::Ok),
+            "cie76" => Some(Self::Cie76),
+            "ciede2000" => Some(Self::Ciede2000),
+            _ => None,
+        }
+    }
+}
+
+/// How the error of replacing a pixel by its nearest palette color is spread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    None,
+    /// Pushes the error onto the next pixels.
+    FloydSteinberg,
+    /// Picks between the two nearest colors with an 8×8 Bayer threshold matrix.
+    Bayer,
+    /// Picks between the two nearest colors with a blue noise threshold tile.
+    BlueNoise,
+}
+
+impl Dither {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Self::None),
+            "floyd-steinberg" => Some(Self::FloydSteinberg),
+            "bayer" => Some(Self::Bayer),
+            "blue-noise" => Some(Self::BlueNoise),
+            _ => None,
+        }
+    }
+}
+
+fn lab(oklab: Vec3) -> Lab {
+    Lab::from_color(Oklab::new(oklab.x, oklab.y, oklab.z))
+}
+
+/// Replaces colors by the nearest of a palette.
+pub struct Remap {
+    metric: DeltaE,
+    /// Oklab.
+    palette: Vec<Vec3>,
+    lab: Vec<Lab>,
+}
+
+impl Remap {
+    /// `palette` holds Oklab colors and must not be empty.
+    pub fn new(palette: &[Vec3], metric: DeltaE) -> Self {
+        Self {
+            metric,
+            palette: palette.to_vec(),
+            lab: palette.iter().map(|&color| lab(color)).collect(),
+        }
+    }
+
+    fn difference(&self, color: Vec3, color_lab: Lab, entry: usize) -> f32 {
+        match self.metric {
+            DeltaE::Ok => (color - self.palette[entry]).magnitude(),
+            DeltaE::Cie76 => {
+                let other = self.lab[entry];
+                vec3(
+                    color_lab.l - other.l,
+                    color_lab.a - other.a,
+                    color_lab.b - other.b,
+                )
+                .magnitude()
+            }
+            DeltaE::Ciede2000 => color_lab.difference(self.lab[entry]),
+        }
+    }
+
+    /// Indices of the nearest and second nearest palette colors.
+    fn nearest(&self, color: Vec3) -> (usize, usize) {
+        let color_lab = match self.metric {
+            DeltaE::Ok => Lab::new(0.0, 0.0, 0.0),
+            DeltaE::Cie76 | DeltaE::Ciede2000 => lab(color),
+        };
+        let mut best = (f32::INFINITY, 0);
+        let mut second = (f32::INFINITY, 0);
+        for entry in 0..self.palette.len() {
+            let difference = self.difference(color, color_lab, entry);
+            if difference < best.0 {
+                second = best;
+                best = (difference, entry);
+            } else if difference < second.0 {
+                second = (difference, entry);
+            }
+        }
+        if second.0.is_infinite() {
+            second = best;
+        }
+        (best.1, second.1)
+    }
+
+    /// Picks the second nearest color where `threshold` is below how far `color` lies towards
+    /// it, so that the colors average out to `color` over an area.
+    fn threshold(&self, color: Vec3, threshold: f32) -> Vec3 {
+        let (first, second) = self.nearest(color);
+        let (a, b) = (self.palette[first], self.palette[second]);
+        let span = (b - a).magnitude2();
+        let t = if span > 0.0 {
+            ((color - a).dot(b - a) / span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        if t > threshold {
+            b
+        } else {
+            a
+        }
+    }
+
+    /// The pixels of `photo` using only palette colors, as Oklab, row by row.
+    pub fn photo(&self, photo: &Photo, dither: Dither) -> Vec<Vec3> {
+        let (width, height) = (photo.width as usize, photo.height as usize);
+        match dither {
+            Dither::None => photo
+                .colors
+                .iter()
+                .map(|&color| self.palette[self.nearest(color).0])
+                .collect(),
+            Dither::FloydSteinberg => {
+                let mut colors = photo.colors.clone();
+                for y in 0..height {
+                    for x in 0..width {
+                        let i = y * width + x;
+                        let old = colors[i];
+                        let new = self.palette[self.nearest(old).0];
+                        colors[i] = new;
+                        let error = old - new;
+                        let mut spread = |dx: isize, dy: usize, weight: f32| {
+                            let x = x as isize + dx;
+                            if x >= 0 && (x as usize) < width && y + dy < height {
+                                colors[(y + dy) * width + x as usize] += error * weight;
+                            }
+                        };
+                        spread(1, 0, 7.0 / 16.0);
+                        spread(-1, 1, 3.0 / 16.0);
+                        spread(0, 1, 5.0 / 16.0);
+                        spread(1, 1, 1.0 / 16.0);
+                    }
+                }
+                colors
+            }
+            Dither::Bayer | Dither::BlueNoise => {
+                let (tile, size) = match dither {
+                    Dither::Bayer => (bayer(), BAYER_SIZE),
+                    _ => (blue_noise(), BLUE_NOISE_SIZE),
+                };
+                photo
+                    .colors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &color)| {
+                        let (x, y) = (i % width, i / width);
+                        self.threshold(color, tile[(y % size) * size + x % size])
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Straight RGBA bytes of Oklab colors, e.g. for an `ImageData`.
+pub fn rgba(colors: &[Vec3]) -> Vec<u8> {
+    colors
+        .iter()
+        .flat_map(|&color| {
+            let srgb = oklab_to_srgb(color);
+            [srgb.x, srgb.y, srgb.z, 1.0].map(|c| (c * 255.0).round() as u8)
+        })
+        .collect()
+}
+
+const BAYER_SIZE: usize = 8;
+
+/// Thresholds in `0..1` of the 8×8 Bayer matrix, row by row.
+fn bayer() -> Vec<f32> {
+    let mut matrix = vec![0u32];
+    let mut size = 1;
+    // Every doubling places the previous matrix in four quadrants, offset by 0, 2, 3 and 1
+    while size < BAYER_SIZE {
+        let mut next = vec![0; size * size * 4];
+        for y in 0..size {
+            for x in 0..size {
+                let value = matrix[y * size + x] * 4;
+                next[y * 2 * size + x] = value;
+                next[y * 2 * size + x + size] = value + 2;
+                next[(y + size) * 2 * size + x] = value + 3;
+                next[(y + size) * 2 * size + x + size] = value + 1;
+            }
+        }
+        matrix = next;
+        size *= 2;
+    }
+    let count = (BAYER_SIZE * BAYER_SIZE) as f32;
+    matrix
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / count)
+        .collect()
+}
+
+const BLUE_NOISE_SIZE: usize = 32;
+
+thread_local! {
+    static BLUE_NOISE: Vec<f32> = void_and_cluster();
+}
+
+/// Thresholds in `0..1` of a tileable blue noise pattern, row by row, made on first use by
+/// void-and-cluster.
+fn blue_noise() -> Vec<f32> {
+    BLUE_NOISE.with(|tile| tile.clone())
+}
+
+fn void_and_cluster() -> Vec<f32> {
+    const SIZE: usize = BLUE_NOISE_SIZE;
+    const COUNT: usize = SIZE * SIZE;
+    const SIGMA: f32 = 1.5;
+
+    // Gaussian falloff on the torus, so the tile repeats seamlessly
+    let falloff: Vec<f32> = (0..COUNT)
+        .map(|i| {
+            let (x, y) = (i % SIZE, i / SIZE);
+            let dx = x.min(SIZE - x) as f32;
+            let dy = y.min(SIZE - y) as f32;
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let update = |energy: &mut [f32], i: usize, sign: f32| {
+        let (x, y) = (i % SIZE, i / SIZE);
+        for (j, energy) in energy.iter_mut().enumerate() {
+            let dx = (j % SIZE + SIZE - x) % SIZE;
+            let dy = (j / SIZE + SIZE - y) % SIZE;
+            *energy += sign * falloff[dy * SIZE + dx];
+        }
+    };
+    // The densest set point and the emptiest unset point
+    let tightest = |pattern: &[bool], energy: &[f32]| {
+        (0..COUNT)
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap_or(0)
+    };
+    let largest_void = |pattern: &[bool], energy: &[f32]| {
+        (0..COUNT)
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap_or(0)
+    };
+
+    // A fixed sprinkling of a tenth of the points, relaxed until no point moves
+    let mut pattern = vec![false; COUNT];
+    let mut energy = vec![0.0; COUNT];
+    let mut seed: u32 = 0x2545_f491;
+    let mut initial = 0;
+    while initial < COUNT / 10 {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        let i = seed as usize % COUNT;
+        if !pattern[i] {
+            pattern[i] = true;
+            update(&mut energy, i, 1.0);
+            initial += 1;
+        }
+    }
+    for _ in 0..COUNT {
+        let cluster = tightest(&pattern, &energy);
+        pattern[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; COUNT];
+    // Points of the initial pattern are ranked by removing the tightest clusters first
+    let mut removing = pattern.clone();
+    let mut removing_energy = energy.clone();
+    for r in (0..initial).rev() {
+        let cluster = tightest(&removing, &removing_energy);
+        removing[cluster] = false;
+        update(&mut removing_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+    // The rest by filling the largest voids
+    for r in initial..COUNT {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+    rank.into_iter()
+        .map(|r| (r as f32 + 0.5) / COUNT as f32)
+        .collect()
+}
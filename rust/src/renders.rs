@@ -1,7 +1,7 @@
-use three_d::{Context, Mat4, Program, RenderStates, RenderTarget, Vec3};
+use three_d::{vec2, Context, Mat4, Program, RenderStates, RenderTarget, Vec3, Viewport};
 
 use crate::{
-    element::{ColorModel, TaggedColorModel},
+    element::{ColorModel, DrawMode, Shading, TaggedColorModel},
     mesh::Mesh,
 };
 
@@ -13,14 +13,37 @@ pub trait Renderer<T> {
     fn render(&mut self, target: &RenderTarget, model: &T);
 }
 
+/// Uniforms widening lines and points, which are measured in pixels of `viewport`.
+fn use_draw_mode(program: &mut Program, model: &ColorModel, viewport: Viewport) {
+    let (primitive, width) = model.draw_mode.uniforms();
+    program.use_uniform_if_required("primitive", primitive);
+    program.use_uniform_if_required("width", width);
+    program.use_uniform_if_required(
+        "viewport",
+        vec2(viewport.width as f32, viewport.height as f32),
+    );
+}
+
+fn use_attributes(program: &mut Program, model: &ColorModel) {
+    for (attribute, buffer) in &model.attributes {
+        if program.requires_attribute(attribute.name()) {
+            program.use_vertex_attribute(attribute.name(), buffer);
+        }
+    }
+}
+
 impl<'a> Renderer<ColorModel<'a>> for Program {
     fn render(&mut self, target: &RenderTarget, model: &ColorModel) {
         target.write(move || {
             self.use_uniform("view", model.view);
             self.use_uniform("model", model.model);
             self.use_uniform("meta", model.meta);
+            self.use_uniform_if_required("shading", model.shading as i32);
+            self.use_uniform_if_required("opacity", model.opacity);
+            use_draw_mode(self, model, target.viewport());
             self.use_vertex_attribute("position", model.positions);
             self.use_vertex_attribute("embed", model.embed);
+            use_attributes(self, model);
             self.draw_elements(model.render_states, target.viewport(), model.indices);
         });
     }
@@ -33,8 +56,10 @@ impl<'a> Renderer<TaggedColorModel<'a>> for Program {
             self.use_uniform("model", model.model.model);
             self.use_uniform("meta", model.model.meta);
             self.use_uniform_if_required("tag", model.tag as f32);
+            use_draw_mode(self, model.model, target.viewport());
             self.use_vertex_attribute("position", model.model.positions);
             self.use_vertex_attribute("embed", model.model.embed);
+            use_attributes(self, model.model);
             self.draw_elements(
                 model.model.render_states,
                 target.viewport(),
@@ -70,9 +95,9 @@ impl Cursor {
 impl<'a> Renderable<'a, CursorState, ColorModel<'a>> for Cursor {
     fn model(&'a self, state: &CursorState) -> ColorModel<'a> {
         ColorModel {
-            positions: &self.positions.vertex_buffer(),
-            embed: &self.positions.vertex_buffer(),
-            indices: &self.positions.element_buffer(),
+            positions: self.positions.vertex_buffer(),
+            embed: self.positions.vertex_buffer(),
+            indices: self.positions.element_buffer(),
             render_states: RenderStates {
                 depth_test: three_d::DepthTest::Always,
                 ..Default::default()
@@ -80,6 +105,10 @@ impl<'a> Renderable<'a, CursorState, ColorModel<'a>> for Cursor {
             view: state.view,
             model: Mat4::from_translation(state.pos) * Mat4::from_scale(0.05),
             meta: Mat4::from_scale(0.0),
+            attributes: Vec::new(),
+            shading: Shading::None,
+            draw_mode: DrawMode::Triangles,
+            opacity: 1.0,
         }
     }
 }
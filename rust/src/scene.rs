@@ -1,7 +1,7 @@
 use std::rc::Rc;
 
 use cgmath::vec3;
-use three_d::{Context, Program, RenderTarget, ScissorBox, Vec3};
+use three_d::{Context, Program, RenderTarget, ScissorBox};
 
 use crate::{
     element::{
@@ -9,10 +9,12 @@ use crate::{
         colorchips::ColorChips,
         colorspace::ColorSpace,
         embedswitcher::EmbedSwitcher,
+        histogram::Histogram,
+        isolines::{IsoLines, IsoSource, RefinedSolid},
+        pointcloud::PointCloud,
         ColorElement, ModelGraph, TaggedColorModel,
     },
-    embed::{CylindricalEmbedding, Embedding, OkhsvEmbedding},
-    pre_embed,
+    mesh::{export::ColoredMesh, subdivide::Refinement, CpuMesh},
     renders::{Cursor, CursorState, Renderable},
     InputState, Renderer,
 };
@@ -36,31 +38,49 @@ pub struct ColorScene {
 }
 
 impl ColorScene {
-    pub fn new(context: &Context) -> Self {
-        let space = pre_embed::cube(48, 6, 2);
-        let space = ColorSpace::new(context, space);
+    pub fn new(context: &Context, solid: Rc<CpuMesh>) -> Self {
+        let space = ColorSpace::new(context, solid.clone(), Refinement::default());
+        let refined = RefinedSolid::shared();
+        let iso_lines = |source, spacing| IsoLines::new(context, refined.clone(), source, spacing);
         Self {
-            cursor: Cursor::cube(&context),
+            cursor: Cursor::cube(context),
             elements: vec![
                 Box::new(space),
-                Box::new(ColorAxis::new(&context, Axis::X)),
-                Box::new(ColorAxis::new(&context, Axis::Y)),
-                Box::new(ColorAxis::new(&context, Axis::Z)),
-                Box::new(ColorChips::new(&context, 6, 0.2)),
-                Box::new(EmbedSwitcher::new(&context, true, 0.0)),
-                Box::new(EmbedSwitcher::new(&context, false, 0.25)),
+                Box::new(iso_lines(IsoSource::Input(Axis::X), 1.0 / 12.0)),
+                Box::new(iso_lines(IsoSource::Color(Axis::X), 0.1)),
+                Box::new(iso_lines(IsoSource::Chroma, 0.05)),
+                Box::new(IsoLines::extra(context, refined.clone())),
+                Box::new(PointCloud::new(context)),
+                Box::new(Histogram::new(context)),
+                Box::new(ColorAxis::new(context, Axis::X)),
+                Box::new(ColorAxis::new(context, Axis::Y)),
+                Box::new(ColorAxis::new(context, Axis::Z)),
+                Box::new(ColorChips::new(context, 6, 0.2)),
+                Box::new(EmbedSwitcher::new(context, true, 0.0)),
+                Box::new(EmbedSwitcher::new(context, false, 0.25)),
             ],
             prev_tag: 0,
         }
     }
 
+    /// Everything drawn in space as one mesh.
+    pub fn export(&self) -> Option<ColoredMesh> {
+        self.elements
+            .iter()
+            .filter_map(|element| element.export())
+            .reduce(|mut mesh, other| {
+                mesh.extend(&other);
+                mesh
+            })
+    }
+
     pub fn render_graph(&self, target: &mut Target, graph: &ModelGraph, tag: u16) {
         match graph {
             ModelGraph::Color(model) => {
                 target.program.render(target.target, model);
             }
             ModelGraph::Space(model) => {
-                let tagged_model = TaggedColorModel { model: model, tag };
+                let tagged_model = TaggedColorModel { model, tag };
                 target.pos_program.render(target.pos_target, &tagged_model);
             }
             ModelGraph::Vec(models) => {
@@ -0,0 +1,406 @@
+use derive_more::Display;
+
+/// Named GLSL modules that can be pulled into a program with `#include "name"`.
+const MODULES: &[(&str, &str)] = &[
+    ("common", include_str!("shader/common.glsl")),
+    ("convert", include_str!("shader/convert.glsl")),
+    ("gamut", include_str!("shader/gamut.glsl")),
+    ("pick", include_str!("shader/pick.glsl")),
+    ("shade", include_str!("shader/shade.glsl")),
+];
+
+pub const COLOR_VERT: &str = include_str!("color.vert");
+
+/// The color program body the view starts with.
+pub const DEFAULT_COLOR: &str = "color = vec4(oklab_to_srgb(pos.xyz), 1.0);";
+
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum ShaderError {
+    #[display(fmt = "unknown shader module \"{}\"", _0)]
+    UnknownModule(String),
+    #[display(fmt = "shader modules include each other: {}", _0)]
+    IncludeCycle(String),
+    #[display(fmt = "malformed include: {}", _0)]
+    MalformedInclude(String),
+    #[display(fmt = "{}", _0)]
+    Invalid(String),
+}
+
+pub fn module(name: &str) -> Option<&'static str> {
+    MODULES
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(_, src)| *src)
+}
+
+/// Builds the fragment shader used by the color and pick programs.
+///
+/// The generated source has no `#version` line, `Program::from_source` adds it.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderBuilder {
+    flags: Vec<String>,
+    modules: Vec<String>,
+    body: String,
+}
+
+impl ShaderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fragment shader coloring `pos`, interpreted as Oklab, with `body`. The result is shaded
+    /// and faded when the model asks for it.
+    pub fn color(body: &str) -> Self {
+        Self::new()
+            .module("convert")
+            .module("shade")
+            .flag("SHADE")
+            .flag("OPACITY")
+            .main(body)
+    }
+
+    /// Fragment shader writing `pos` and the element tag for picking.
+    pub fn pick() -> Self {
+        Self::new().module("pick").main("color = pick(pos);")
+    }
+
+    /// Toggles an `#ifdef` block, e.g. `MARK_GAMUT`.
+    pub fn flag(mut self, name: &str) -> Self {
+        if !self.has_flag(name) {
+            self.flags.push(name.to_string());
+        }
+        self
+    }
+
+    pub fn module(mut self, name: &str) -> Self {
+        if !self.modules.iter().any(|module| module == name) {
+            self.modules.push(name.to_string());
+        }
+        self
+    }
+
+    pub fn main(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self
+    }
+
+    fn has_flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    pub fn build(&self) -> Result<String, ShaderError> {
+        let mut src = String::new();
+        for name in &self.flags {
+            src.push_str(&format!("#define {}\n", name));
+        }
+        src.push_str("\nin vec3 pos;\n\nlayout (location = 0) out vec4 color;\n");
+        if self.has_flag("OPACITY") {
+            src.push_str("uniform float opacity;\n");
+        }
+
+        let mut included = Vec::new();
+        let mut modules = self.modules.clone();
+        if self.has_flag("MARK_GAMUT") && !modules.iter().any(|module| module == "gamut") {
+            modules.push("gamut".to_string());
+        }
+        for name in &modules {
+            include(name, &mut included, &mut Vec::new(), &mut src)?;
+        }
+
+        // Source string 1 marks the body so compile errors can be traced back to it.
+        src.push_str("\nvoid main() {\n#line 1 1\n");
+        src.push_str(&self.body);
+        src.push_str("\n#line 1 0\n#ifdef SHADE\n    color.rgb = shade(color.rgb);\n#endif\n#ifdef OPACITY\n    color.a *= opacity;\n#endif\n#ifdef MARK_GAMUT\n    color.rgb = mark_out_of_gamut(color.rgb);\n#endif\n}\n");
+        Ok(src)
+    }
+}
+
+/// Appends `name` to `src`, resolving its includes first. Each module is only emitted once.
+fn include(
+    name: &str,
+    included: &mut Vec<String>,
+    stack: &mut Vec<String>,
+    src: &mut String,
+) -> Result<(), ShaderError> {
+    if included.iter().any(|module| module == name) {
+        return Ok(());
+    }
+    if stack.iter().any(|module| module == name) {
+        stack.push(name.to_string());
+        return Err(ShaderError::IncludeCycle(stack.join(" -> ")));
+    }
+    let module_src = module(name).ok_or_else(|| ShaderError::UnknownModule(name.to_string()))?;
+    stack.push(name.to_string());
+    let mut body = String::new();
+    for line in module_src.lines() {
+        let trimmed = line.trim();
+        if let Some(rest) = trimmed.strip_prefix("#include") {
+            let dependency = rest
+                .trim()
+                .strip_prefix('"')
+                .and_then(|rest| rest.strip_suffix('"'))
+                .ok_or_else(|| ShaderError::MalformedInclude(trimmed.to_string()))?;
+            include(dependency, included, stack, src)?;
+        } else {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+    stack.pop();
+    src.push_str(&format!("\n// module: {}\n", name));
+    src.push_str(&body);
+    included.push(name.to_string());
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: Option<u32>,
+    /// Whether `line` is relative to the builder body rather than the generated source.
+    pub in_expression: bool,
+    pub message: String,
+}
+
+/// Splits a shader error into one diagnostic per reported error.
+///
+/// Understands the `ERROR: 1:3: ...` logs from ANGLE and the `1:3(12): error: ...` logs from Mesa.
+pub fn diagnostics(error: &ShaderError) -> Vec<Diagnostic> {
+    let log = match error {
+        ShaderError::Invalid(log) => log,
+        error => {
+            return vec![Diagnostic {
+                line: None,
+                in_expression: false,
+                message: error.to_string(),
+            }]
+        }
+    };
+    let diagnostics: Vec<Diagnostic> = log
+        .lines()
+        .filter(|line| line.to_lowercase().contains("error"))
+        .map(|line| match source_location(line) {
+            Some((source, number, rest)) => Diagnostic {
+                line: Some(number),
+                in_expression: source == 1,
+                message: rest
+                    .trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+                    .trim_start_matches("error:")
+                    .trim()
+                    .to_string(),
+            },
+            None => Diagnostic {
+                line: None,
+                in_expression: false,
+                message: line.trim().to_string(),
+            },
+        })
+        .collect();
+    if diagnostics.is_empty() {
+        vec![Diagnostic {
+            line: None,
+            in_expression: false,
+            message: log.trim().to_string(),
+        }]
+    } else {
+        diagnostics
+    }
+}
+
+/// Finds the first `source:line` pair in a log line, returning what follows it.
+fn source_location(line: &str) -> Option<(u32, u32, &str)> {
+    let bytes = line.as_bytes();
+    let mut start = 0;
+    while start < bytes.len() {
+        if !bytes[start].is_ascii_digit() || (start > 0 && bytes[start - 1].is_ascii_digit()) {
+            start += 1;
+            continue;
+        }
+        let colon = start + line[start..].find(|c: char| !c.is_ascii_digit())?;
+        if bytes[colon] != b':' {
+            start = colon;
+            continue;
+        }
+        let number_start = colon + 1;
+        let number_end = number_start
+            + line[number_start..]
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(line.len() - number_start);
+        if number_end == number_start {
+            start = number_start;
+            continue;
+        }
+        let source = line[start..colon].parse().ok()?;
+        let number = line[number_start..number_end].parse().ok()?;
+        let rest = line[number_end..]
+            .trim_start_matches(|c: char| c.is_ascii_digit() || c == '(' || c == ')');
+        return Some((source, number, rest));
+    }
+    None
+}
+
+/// Checks a vertex and fragment shader pair with naga's GLSL frontend, without needing a GPU.
+///
+/// naga only accepts Vulkan flavoured GLSL, so loose uniforms are moved into blocks and
+/// varyings get explicit locations before parsing.
+#[cfg(feature = "validate")]
+pub fn validate(vertex: &str, fragment: &str) -> Result<(), ShaderError> {
+    use naga::{
+        front::glsl::{Frontend, Options},
+        valid::{Capabilities, ValidationFlags, Validator},
+        ShaderStage,
+    };
+
+    let mut frontend = Frontend::default();
+    for (stage, src) in [
+        (ShaderStage::Vertex, vertex),
+        (ShaderStage::Fragment, fragment),
+    ] {
+        let src = vulkan_glsl(src);
+        let module = frontend
+            .parse(&Options::from(stage), &src)
+            .map_err(|errors| {
+                ShaderError::Invalid(
+                    errors
+                        .iter()
+                        .map(|error| error.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                )
+            })?;
+        Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .map_err(|error| ShaderError::Invalid(format!("{:?}", error)))?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "validate")]
+fn vulkan_glsl(src: &str) -> String {
+    let mut out = String::from("#version 450\n");
+    let mut binding = 0;
+    let mut input = 0;
+    let mut output = 0;
+    for line in src.lines() {
+        let trimmed = line.trim();
+        let words: Vec<&str> = trimmed.trim_end_matches(';').split_whitespace().collect();
+        match words.as_slice() {
+            ["uniform", ty, name] => {
+                out.push_str(&format!(
+                    "layout(set = 0, binding = {}) uniform Block_{} {{ {} {}; }};\n",
+                    binding, name, ty, name
+                ));
+                binding += 1;
+            }
+            ["in", ty, name] => {
+                out.push_str(&format!(
+                    "layout(location = {}) in {} {};\n",
+                    input, ty, name
+                ));
+                input += 1;
+            }
+            ["out", ty, name] => {
+                out.push_str(&format!(
+                    "layout(location = {}) out {} {};\n",
+                    output, ty, name
+                ));
+                output += 1;
+            }
+            _ => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every fragment shader the view builds.
+    fn programs() -> Vec<ShaderBuilder> {
+        vec![
+            ShaderBuilder::color(DEFAULT_COLOR),
+            ShaderBuilder::color(DEFAULT_COLOR).flag("MARK_GAMUT"),
+            ShaderBuilder::pick(),
+        ]
+    }
+
+    #[test]
+    fn builds_every_program() {
+        for program in programs() {
+            let src = program.build().unwrap();
+            assert!(src.contains("void main()"));
+            // Modules shared by several dependencies are emitted once
+            let modules: Vec<&str> = src
+                .lines()
+                .filter(|l| l.starts_with("// module: "))
+                .collect();
+            let mut unique = modules.clone();
+            unique.sort_unstable();
+            unique.dedup();
+            assert_eq!(modules.len(), unique.len());
+        }
+    }
+
+    #[test]
+    fn includes_dependencies_before_modules() {
+        let src = ShaderBuilder::color(DEFAULT_COLOR).build().unwrap();
+        let convert = src.find("// module: convert").unwrap();
+        let shade = src.find("// module: shade").unwrap();
+        assert!(convert < shade);
+    }
+
+    #[test]
+    fn marks_gamut_only_when_flagged() {
+        let plain = ShaderBuilder::color(DEFAULT_COLOR).build().unwrap();
+        let marked = ShaderBuilder::color(DEFAULT_COLOR)
+            .flag("MARK_GAMUT")
+            .build()
+            .unwrap();
+        assert!(!plain.contains("// module: gamut"));
+        assert!(marked.contains("#define MARK_GAMUT\n"));
+        assert!(marked.contains("// module: gamut"));
+    }
+
+    #[test]
+    fn rejects_unknown_modules() {
+        let error = ShaderBuilder::new().module("missing").build().unwrap_err();
+        assert_eq!(error, ShaderError::UnknownModule("missing".to_string()));
+    }
+
+    #[test]
+    fn reads_angle_and_mesa_logs() {
+        let angle = ShaderError::Invalid("ERROR: 1:3: 'x' : undeclared identifier".to_string());
+        let diagnostic = &diagnostics(&angle)[0];
+        assert_eq!(diagnostic.line, Some(3));
+        assert!(diagnostic.in_expression);
+
+        let mesa = ShaderError::Invalid("0:12(5): error: syntax error".to_string());
+        let diagnostic = &diagnostics(&mesa)[0];
+        assert_eq!(diagnostic.line, Some(12));
+        assert!(!diagnostic.in_expression);
+        assert_eq!(diagnostic.message, "syntax error");
+    }
+
+    #[cfg(feature = "validate")]
+    #[test]
+    fn validates_every_program() {
+        for program in programs() {
+            let src = program.build().unwrap();
+            if let Err(error) = validate(COLOR_VERT, &src) {
+                panic!("{}\n{}", error, src);
+            }
+        }
+    }
+
+    #[cfg(feature = "validate")]
+    #[test]
+    fn rejects_broken_bodies() {
+        let src = ShaderBuilder::color("color = undefined_function(pos);")
+            .build()
+            .unwrap();
+        assert!(validate(COLOR_VERT, &src).is_err());
+    }
+}
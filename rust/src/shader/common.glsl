@@ -0,0 +1,9 @@
+#define PI 3.141592653
+
+float max3 (vec3 v) {
+  return max (max (v.x, v.y), v.z);
+}
+
+float min3 (vec3 v) {
+  return min (min (v.x, v.y), v.z);
+}
@@ -1,11 +1,4 @@
-
-#define PI 3.141592653
-
-in vec3 pos;
-
-uniform float tag;
-
-layout (location = 0) out vec4 color;
+#include "common"
 
 vec3 xyz2hsv(vec3 pos)
 {
@@ -51,21 +44,11 @@ vec3 linear_srgb_to_srgb(vec3 rgb) {
     return mix(lo, hi, select);
 }
 
-float max3 (vec3 v) {
-  return max (max (v.x, v.y), v.z);
-}
-
-float min3 (vec3 v) {
-  return min (min (v.x, v.y), v.z);
-}
-
-vec3 mark_out_of_gamut(vec3 rgb) {
-    float mn = min3(rgb);
-    float mx = max3(rgb);
-    float below = 1.0 - smoothstep(-0.01, 0.0, mn);
-    float above = smoothstep(1.0, 1.01, mx);
-    float outside = max(below, above);
-    return mix(rgb, vec3(0.5, 0.0, 0.5), outside);
+vec3 srgb_to_linear_srgb(vec3 rgb) {
+    vec3 select = step(vec3(0.04045, 0.04045, 0.04045), rgb);
+    vec3 lo = rgb / 12.92;
+    vec3 hi = pow((rgb + 0.055) / 1.055, vec3(2.4, 2.4, 2.4));
+    return mix(lo, hi, select);
 }
 
 vec3 oklab_to_linear_srgb(vec3 lab) {
@@ -93,11 +76,3 @@ vec3 linear_srgb_to_oklab(vec3 rgb) {
 vec3 oklab_to_srgb(vec3 oklab) {
     return linear_srgb_to_srgb(oklab_to_linear_srgb(oklab));
 }
-
-vec3 mark_oklab_to_srgb(vec3 oklab) {
-    return mark_out_of_gamut(oklab_to_srgb(oklab));
-}
-
-void main(){
-    // REPLACE
-}
\ No newline at end of file
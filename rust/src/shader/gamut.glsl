@@ -0,0 +1,15 @@
+#include "common"
+#include "convert"
+
+vec3 mark_out_of_gamut(vec3 rgb) {
+    float mn = min3(rgb);
+    float mx = max3(rgb);
+    float below = 1.0 - smoothstep(-0.01, 0.0, mn);
+    float above = smoothstep(1.0, 1.01, mx);
+    float outside = max(below, above);
+    return mix(rgb, vec3(0.5, 0.0, 0.5), outside);
+}
+
+vec3 mark_oklab_to_srgb(vec3 oklab) {
+    return mark_out_of_gamut(oklab_to_srgb(oklab));
+}
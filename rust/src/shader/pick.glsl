@@ -0,0 +1,6 @@
+uniform float tag;
+
+// Writes the input space position and the element tag to the pick target.
+vec4 pick(vec3 pos) {
+    return vec4(pos, tag);
+}
@@ -0,0 +1,24 @@
+#include "convert"
+
+// 0 leaves colors exact, 1 is soft diffuse light and 2 darkens silhouettes
+uniform int shading;
+in vec3 v_normal;
+in vec3 v_view;
+
+// Scales linear light, so hue and the ratio of chroma to lightness stay put.
+// Normals are used two-sided, windings are not consistent across solids.
+vec3 shade(vec3 srgb) {
+    if (shading == 0) {
+        return srgb;
+    }
+    vec3 n = normalize(v_normal);
+    float k = 1.0;
+    if (shading == 1) {
+        float diffuse = abs(dot(n, normalize(vec3(0.3, 1.0, 0.5))));
+        k = mix(0.7, 1.0, diffuse);
+    } else if (shading == 2) {
+        float facing = abs(dot(n, normalize(v_view)));
+        k = mix(0.55, 1.0, sqrt(facing));
+    }
+    return linear_srgb_to_srgb(srgb_to_linear_srgb(srgb) * k);
+}